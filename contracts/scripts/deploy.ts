@@ -2,7 +2,12 @@ import { getDeploymentData } from '@/utils/getDeploymentData'
 import { initPolkadotJs } from '@/utils/initPolkadotJs'
 import { writeContractAddresses } from '@/utils/writeContractAddresses'
 import { ContractPromise } from '@polkadot/api-contract'
-import { contractTx, deployContract } from '@scio-labs/use-inkathon/helpers'
+import {
+  contractQuery,
+  contractTx,
+  decodeOutput,
+  deployContract,
+} from '@scio-labs/use-inkathon/helpers'
 import * as dotenv from 'dotenv'
 
 const PRICE_ORACLE_ADDRESS_MAINNET = '5F7wPCMXX65RmL8oiuAFNKu2ydhvgcissDZ3NWZ5X85n2WPG'
@@ -97,12 +102,9 @@ const deploy_market = async () => {
   return market.hash
 }
 
-const deploy_market_with_manager = async (
+const set_market_template = async (
   managerAddress,
   marketHash,
-  name,
-  symbol,
-  underlyingAsset,
   liquidationThreshold,
   liquidationPenalty,
   protocolFee,
@@ -113,18 +115,51 @@ const deploy_market_with_manager = async (
   const { abi, wasm } = await getDeploymentData('manager')
   const contract = new ContractPromise(api, abi, managerAddress)
   const params = [
-    marketHash,
-    name,
-    symbol,
-    underlyingAsset,
-    liquidationThreshold,
-    liquidationPenalty,
-    protocolFee,
+    {
+      codeHash: marketHash,
+      liquidationThreshold,
+      liquidationPenalty,
+      protocolFee,
+    },
   ]
 
+  await contractTx(api, account, contract, 'set_market_template', {}, params)
+}
+
+const deploy_market_with_manager = async (managerAddress, underlyingAsset) => {
+  const initParams = await initPolkadotJs()
+  const { api, chain, account } = initParams
+
+  const { abi, wasm } = await getDeploymentData('manager')
+  const contract = new ContractPromise(api, abi, managerAddress)
+  const params = [underlyingAsset]
+
   await contractTx(api, account, contract, 'deploy_market', {}, params)
 }
 
+const set_market_oracle_pair = async (managerAddress, marketAddress, asset, pair, decimals) => {
+  const initParams = await initPolkadotJs()
+  const { api, chain, account } = initParams
+
+  const { abi, wasm } = await getDeploymentData('manager')
+  const contract = new ContractPromise(api, abi, managerAddress)
+  const params = [marketAddress, asset, pair, decimals]
+
+  await contractTx(api, account, contract, 'set_market_oracle_pair', {}, params)
+}
+
+const view_markets = async (managerAddress) => {
+  const initParams = await initPolkadotJs()
+  const { api, chain, account } = initParams
+
+  const { abi, wasm } = await getDeploymentData('manager')
+  const contract = new ContractPromise(api, abi, managerAddress)
+  const result = await contractQuery(api, account.address, contract, 'view_markets')
+  const { output } = decodeOutput(result, contract, 'view_markets')
+
+  return output
+}
+
 const add_asset_to_vault = async (managerAddress, asset) => {
   const initParams = await initPolkadotJs()
   const { api, chain, account } = initParams
@@ -171,16 +206,11 @@ const main = async () => {
     const managerAddress = await deploy_manager(1, vaultHash, wazeroAddress, fakerAddress)
 
     await add_asset_to_vault(managerAddress, wazeroAddress)
-    await deploy_market_with_manager(
-      managerAddress,
-      marketHash,
-      'Wrapped Azero',
-      'WAZERO',
-      wazeroAddress,
-      -60,
-      10,
-      5,
-    )
+    await set_market_template(managerAddress, marketHash, -6000, 1000, 500)
+    await deploy_market_with_manager(managerAddress, wazeroAddress)
+
+    const [marketAddress] = await view_markets(managerAddress)
+    await set_market_oracle_pair(managerAddress, marketAddress, wazeroAddress, 'AZERO/USD', 18)
 
     console.log('\nDeployments completed successfully')
   } catch (error) {
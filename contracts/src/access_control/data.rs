@@ -0,0 +1,203 @@
+use ink::{
+    prelude::{vec, vec::Vec},
+    primitives::AccountId,
+    storage::Mapping,
+};
+
+use crate::{errors::AccessControlError, Role};
+
+/// Temporary type for events emitted during operations that change the
+/// ownership or roles of a contract.
+/// Contracts using `AccessControlData` should convert these into their own events.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AccessControlEvent {
+    OwnershipTransferStarted {
+        previous_owner: AccountId,
+        new_owner: AccountId,
+    },
+    OwnershipTransferred {
+        previous_owner: AccountId,
+        new_owner: AccountId,
+    },
+    RoleGranted {
+        role: Role,
+        account: AccountId,
+        sender: AccountId,
+    },
+    RoleRevoked {
+        role: Role,
+        account: AccountId,
+        sender: AccountId,
+    },
+}
+
+/// A class implementing two-step ownership transfers and role based access control.
+/// The owner passes every role check, accounts with `Role::Admin` pass every role
+/// check apart from ownership transfers.
+#[ink::storage_item]
+#[derive(Debug)]
+pub struct AccessControlData {
+    owner: AccountId,
+    pending_owner: Option<AccountId>,
+    roles: Mapping<(Role, AccountId), ()>,
+}
+
+impl AccessControlData {
+    /// Creates a new access control data with `owner` as the owner.
+    pub fn new(owner: AccountId) -> AccessControlData {
+        AccessControlData {
+            owner,
+            pending_owner: None,
+            roles: Default::default(),
+        }
+    }
+
+    pub fn owner(&self) -> AccountId {
+        self.owner
+    }
+
+    pub fn pending_owner(&self) -> Option<AccountId> {
+        self.pending_owner
+    }
+
+    /// Returns true if `account` is the owner or has been granted `role`.
+    pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+        account == self.owner || self.roles.contains((role, account))
+    }
+
+    pub fn ensure_owner(&self, caller: AccountId) -> Result<(), AccessControlError> {
+        if caller != self.owner {
+            return Err(AccessControlError::NotOwner);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `caller` has `role` or is an admin.
+    pub fn ensure_role(&self, caller: AccountId, role: Role) -> Result<(), AccessControlError> {
+        if !self.has_role(role, caller) && !self.has_role(Role::Admin, caller) {
+            return Err(AccessControlError::MissingRole(role));
+        }
+
+        Ok(())
+    }
+
+    /// Starts the transfer of ownership to `new_owner`, which needs to be
+    /// accepted by `new_owner` before it takes effect.
+    pub fn transfer_ownership(
+        &mut self,
+        caller: AccountId,
+        new_owner: AccountId,
+    ) -> Result<Vec<AccessControlEvent>, AccessControlError> {
+        self.ensure_owner(caller)?;
+
+        self.pending_owner = Some(new_owner);
+
+        Ok(vec![AccessControlEvent::OwnershipTransferStarted {
+            previous_owner: caller,
+            new_owner,
+        }])
+    }
+
+    /// Completes the transfer of ownership started by `transfer_ownership`.
+    pub fn accept_ownership(
+        &mut self,
+        caller: AccountId,
+    ) -> Result<Vec<AccessControlEvent>, AccessControlError> {
+        if self.pending_owner != Some(caller) {
+            return Err(AccessControlError::NotPendingOwner);
+        }
+
+        let previous_owner = self.owner;
+        self.owner = caller;
+        self.pending_owner = None;
+
+        Ok(vec![AccessControlEvent::OwnershipTransferred {
+            previous_owner,
+            new_owner: caller,
+        }])
+    }
+
+    /// Gives up the ownership, leaving the contract without an owner. Roles granted
+    /// before stay in place.
+    pub fn renounce_ownership(
+        &mut self,
+        caller: AccountId,
+    ) -> Result<Vec<AccessControlEvent>, AccessControlError> {
+        self.ensure_owner(caller)?;
+
+        let new_owner = AccountId::from([0u8; 32]);
+        self.owner = new_owner;
+        self.pending_owner = None;
+
+        Ok(vec![AccessControlEvent::OwnershipTransferred {
+            previous_owner: caller,
+            new_owner,
+        }])
+    }
+
+    /// Grants `role` to `account`. Only the owner and admins can grant roles,
+    /// and only the owner can grant `Role::Admin`.
+    pub fn grant_role(
+        &mut self,
+        caller: AccountId,
+        role: Role,
+        account: AccountId,
+    ) -> Result<Vec<AccessControlEvent>, AccessControlError> {
+        self.ensure_role_admin(caller, role)?;
+
+        if self.roles.contains((role, account)) {
+            return Ok(vec![]);
+        }
+        self.roles.insert((role, account), &());
+
+        Ok(vec![AccessControlEvent::RoleGranted {
+            role,
+            account,
+            sender: caller,
+        }])
+    }
+
+    /// Revokes `role` from `account`. Same permissions as `grant_role` apply.
+    pub fn revoke_role(
+        &mut self,
+        caller: AccountId,
+        role: Role,
+        account: AccountId,
+    ) -> Result<Vec<AccessControlEvent>, AccessControlError> {
+        self.ensure_role_admin(caller, role)?;
+
+        Ok(self.remove_role(caller, role, account))
+    }
+
+    /// Removes `role` from the caller.
+    pub fn renounce_role(&mut self, caller: AccountId, role: Role) -> Vec<AccessControlEvent> {
+        self.remove_role(caller, role, caller)
+    }
+
+    fn ensure_role_admin(&self, caller: AccountId, role: Role) -> Result<(), AccessControlError> {
+        if role == Role::Admin {
+            return self.ensure_owner(caller);
+        }
+
+        self.ensure_role(caller, Role::Admin)
+    }
+
+    fn remove_role(
+        &mut self,
+        caller: AccountId,
+        role: Role,
+        account: AccountId,
+    ) -> Vec<AccessControlEvent> {
+        if !self.roles.contains((role, account)) {
+            return vec![];
+        }
+        self.roles.remove((role, account));
+
+        vec![AccessControlEvent::RoleRevoked {
+            role,
+            account,
+            sender: caller,
+        }]
+    }
+}
@@ -0,0 +1,12 @@
+use crate::Role;
+
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum AccessControlError {
+    /// Returned when the caller is not the owner.
+    NotOwner,
+    /// Returned when the caller is not the pending owner.
+    NotPendingOwner,
+    /// Returned when the caller is missing the required role.
+    MissingRole(Role),
+}
@@ -0,0 +1,13 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod data;
+mod errors;
+mod role;
+#[cfg(test)]
+mod testing;
+mod traits;
+
+pub use data::{AccessControlData, AccessControlEvent};
+pub use errors::AccessControlError;
+pub use role::Role;
+pub use traits::AccessControl;
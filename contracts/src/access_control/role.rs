@@ -0,0 +1,10 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum Role {
+    /// Can change configuration and manage roles of other accounts.
+    Admin,
+    /// Can pause and unpause the protocol during incidents.
+    Guardian,
+    /// Can claim accrued protocol fees.
+    FeeCollector,
+}
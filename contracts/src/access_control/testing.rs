@@ -0,0 +1,207 @@
+use ink::env::{test::*, DefaultEnvironment as E};
+use ink::primitives::AccountId;
+
+use crate::{AccessControlData, AccessControlError, AccessControlEvent, Role};
+
+#[ink::test]
+fn constructor_works() {
+    let acc = default_accounts::<E>();
+    let data = AccessControlData::new(acc.alice);
+
+    assert_eq!(data.owner(), acc.alice);
+    assert_eq!(data.pending_owner(), None);
+    assert!(data.has_role(Role::Admin, acc.alice));
+    assert!(!data.has_role(Role::Guardian, acc.bob));
+}
+
+#[ink::test]
+fn transfer_ownership_is_two_step() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+
+    let events = data.transfer_ownership(acc.alice, acc.bob).unwrap();
+    assert_eq!(
+        events,
+        vec![AccessControlEvent::OwnershipTransferStarted {
+            previous_owner: acc.alice,
+            new_owner: acc.bob,
+        }]
+    );
+    assert_eq!(data.owner(), acc.alice);
+    assert_eq!(data.pending_owner(), Some(acc.bob));
+
+    let events = data.accept_ownership(acc.bob).unwrap();
+    assert_eq!(
+        events,
+        vec![AccessControlEvent::OwnershipTransferred {
+            previous_owner: acc.alice,
+            new_owner: acc.bob,
+        }]
+    );
+    assert_eq!(data.owner(), acc.bob);
+    assert_eq!(data.pending_owner(), None);
+    assert_eq!(
+        data.ensure_owner(acc.alice),
+        Err(AccessControlError::NotOwner)
+    );
+}
+
+#[ink::test]
+fn transfer_ownership_fails_for_non_owner() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+
+    assert_eq!(
+        data.transfer_ownership(acc.bob, acc.bob),
+        Err(AccessControlError::NotOwner)
+    );
+    assert_eq!(data.pending_owner(), None);
+}
+
+#[ink::test]
+fn accept_ownership_checks_pending_owner() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+
+    assert_eq!(
+        data.accept_ownership(acc.bob),
+        Err(AccessControlError::NotPendingOwner)
+    );
+
+    data.transfer_ownership(acc.alice, acc.bob).unwrap();
+    assert_eq!(
+        data.accept_ownership(acc.charlie),
+        Err(AccessControlError::NotPendingOwner)
+    );
+    assert_eq!(data.owner(), acc.alice);
+    assert_eq!(data.pending_owner(), Some(acc.bob));
+}
+
+#[ink::test]
+fn renounce_ownership_works() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+    let zero = AccountId::from([0u8; 32]);
+
+    assert_eq!(
+        data.renounce_ownership(acc.bob),
+        Err(AccessControlError::NotOwner)
+    );
+
+    data.transfer_ownership(acc.alice, acc.bob).unwrap();
+    let events = data.renounce_ownership(acc.alice).unwrap();
+    assert_eq!(
+        events,
+        vec![AccessControlEvent::OwnershipTransferred {
+            previous_owner: acc.alice,
+            new_owner: zero,
+        }]
+    );
+    assert_eq!(data.owner(), zero);
+    assert_eq!(data.pending_owner(), None);
+    assert!(!data.has_role(Role::Admin, acc.alice));
+}
+
+#[ink::test]
+fn only_owner_grants_admin() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+
+    let events = data.grant_role(acc.alice, Role::Admin, acc.bob).unwrap();
+    assert_eq!(
+        events,
+        vec![AccessControlEvent::RoleGranted {
+            role: Role::Admin,
+            account: acc.bob,
+            sender: acc.alice,
+        }]
+    );
+
+    // admins can grant other roles, but not admin
+    assert_eq!(
+        data.grant_role(acc.bob, Role::Admin, acc.charlie),
+        Err(AccessControlError::NotOwner)
+    );
+    assert!(data.grant_role(acc.bob, Role::FeeCollector, acc.charlie).is_ok());
+    assert!(data.has_role(Role::FeeCollector, acc.charlie));
+
+    assert_eq!(
+        data.grant_role(acc.charlie, Role::Guardian, acc.django),
+        Err(AccessControlError::MissingRole(Role::Admin))
+    );
+}
+
+#[ink::test]
+fn grant_role_twice_emits_once() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+
+    assert_eq!(
+        data.grant_role(acc.alice, Role::Guardian, acc.bob).unwrap().len(),
+        1
+    );
+    assert!(data
+        .grant_role(acc.alice, Role::Guardian, acc.bob)
+        .unwrap()
+        .is_empty());
+}
+
+#[ink::test]
+fn ensure_role_accepts_admin() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+
+    assert_eq!(
+        data.ensure_role(acc.bob, Role::FeeCollector),
+        Err(AccessControlError::MissingRole(Role::FeeCollector))
+    );
+    data.grant_role(acc.alice, Role::Admin, acc.bob).unwrap();
+    assert!(data.ensure_role(acc.bob, Role::FeeCollector).is_ok());
+    assert!(!data.has_role(Role::FeeCollector, acc.bob));
+}
+
+#[ink::test]
+fn revoke_role_works() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+    data.grant_role(acc.alice, Role::Guardian, acc.bob).unwrap();
+
+    assert_eq!(
+        data.revoke_role(acc.charlie, Role::Guardian, acc.bob),
+        Err(AccessControlError::MissingRole(Role::Admin))
+    );
+
+    let events = data.revoke_role(acc.alice, Role::Guardian, acc.bob).unwrap();
+    assert_eq!(
+        events,
+        vec![AccessControlEvent::RoleRevoked {
+            role: Role::Guardian,
+            account: acc.bob,
+            sender: acc.alice,
+        }]
+    );
+    assert!(!data.has_role(Role::Guardian, acc.bob));
+    assert!(data
+        .revoke_role(acc.alice, Role::Guardian, acc.bob)
+        .unwrap()
+        .is_empty());
+}
+
+#[ink::test]
+fn renounce_role_works() {
+    let acc = default_accounts::<E>();
+    let mut data = AccessControlData::new(acc.alice);
+    data.grant_role(acc.alice, Role::Guardian, acc.bob).unwrap();
+
+    let events = data.renounce_role(acc.bob, Role::Guardian);
+    assert_eq!(
+        events,
+        vec![AccessControlEvent::RoleRevoked {
+            role: Role::Guardian,
+            account: acc.bob,
+            sender: acc.bob,
+        }]
+    );
+    assert!(!data.has_role(Role::Guardian, acc.bob));
+    assert!(data.renounce_role(acc.bob, Role::Guardian).is_empty());
+}
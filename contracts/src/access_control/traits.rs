@@ -0,0 +1,87 @@
+use ink::primitives::AccountId;
+
+use crate::{errors::AccessControlError, Role};
+
+#[ink::trait_definition]
+pub trait AccessControl {
+    /// Returns the current owner.
+    #[ink(message)]
+    fn owner(&self) -> AccountId;
+
+    /// Returns the account that can accept the ownership, if a transfer is in progress.
+    #[ink(message)]
+    fn pending_owner(&self) -> Option<AccountId>;
+
+    /// Returns true if `account` is the owner or has been granted `role`.
+    #[ink(message)]
+    fn has_role(&self, role: Role, account: AccountId) -> bool;
+
+    /// Starts the transfer of ownership to `new_owner`.
+    ///
+    /// # Events
+    ///
+    /// On success an `OwnershipTransferStarted` event is emitted.
+    ///
+    /// # Errors
+    ///
+    /// Reverts with `NotOwner` if the caller is not the owner.
+    #[ink(message)]
+    fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), AccessControlError>;
+
+    /// Accepts a pending ownership transfer.
+    ///
+    /// # Events
+    ///
+    /// On success an `OwnershipTransferred` event is emitted.
+    ///
+    /// # Errors
+    ///
+    /// Reverts with `NotPendingOwner` if the caller is not the pending owner.
+    #[ink(message)]
+    fn accept_ownership(&mut self) -> Result<(), AccessControlError>;
+
+    /// Gives up the ownership, leaving the contract without an owner.
+    ///
+    /// # Events
+    ///
+    /// On success an `OwnershipTransferred` event to the zero address is emitted.
+    ///
+    /// # Errors
+    ///
+    /// Reverts with `NotOwner` if the caller is not the owner.
+    #[ink(message)]
+    fn renounce_ownership(&mut self) -> Result<(), AccessControlError>;
+
+    /// Grants `role` to `account`.
+    ///
+    /// # Events
+    ///
+    /// On success a `RoleGranted` event is emitted, unless `account` already had `role`.
+    ///
+    /// # Errors
+    ///
+    /// Reverts with `MissingRole(Admin)` if the caller is not an admin, or with
+    /// `NotOwner` if `role` is `Admin` and the caller is not the owner.
+    #[ink(message)]
+    fn grant_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError>;
+
+    /// Revokes `role` from `account`.
+    ///
+    /// # Events
+    ///
+    /// On success a `RoleRevoked` event is emitted, unless `account` did not have `role`.
+    ///
+    /// # Errors
+    ///
+    /// Same as `grant_role`.
+    #[ink(message)]
+    fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError>;
+
+    /// Removes `role` from the caller.
+    ///
+    /// # Events
+    ///
+    /// A `RoleRevoked` event is emitted, unless the caller did not have `role`.
+    #[ink(message)]
+    fn renounce_role(&mut self, role: Role) -> Result<(), AccessControlError>;
+}
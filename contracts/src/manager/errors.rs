@@ -1,8 +1,18 @@
+use access_control::AccessControlError;
+use ink::prelude::string::String;
+use market::MarketError;
 use vault::VaultError;
 
 #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
 pub enum ManagerError {
-    NotOwner,
+    AccessControlError(AccessControlError),
+    MarketAlreadyExists,
+    MarketDeprecated,
+    MarketError(MarketError),
+    MarketNotFound,
+    MarketTemplateNotSet,
+    Overflow(String),
+    UpgradeFailed,
     VaultError(VaultError),
 }
@@ -1,29 +1,52 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
 mod errors;
+mod market_info;
+mod market_template;
+mod portfolio;
 
 pub use errors::ManagerError;
+pub use market_info::{MarketInfo, MarketStatus};
+pub use market_template::MarketTemplate;
+pub use portfolio::{MarketStats, Portfolio, PortfolioPosition, ProtocolStats};
 
 #[ink::contract]
 mod manager {
+    use access_control::{AccessControl, AccessControlData, AccessControlError, AccessControlEvent, Role};
     use ink::{
-        contract_ref, prelude::{string::String, vec::Vec}, ToAccountId
+        codegen::EmitEvent, contract_ref, env::{call::FromAccountId, hash::Blake2x256},
+        prelude::{string::String, vec::Vec}, reflect::ContractEventBase, storage::{Lazy, Mapping},
+        ToAccountId
     };
-    use market::MarketRef;
-    use psp22::PSP22Metadata;
-    use vault::{CollateralVault, VaultRef};
+    use market::{ConfigChange, MarketError, MarketRef, PauseScope};
+    use psp22::{PSP22Metadata, PSP22};
+    use scale::Encode;
+    use vault::{CollateralVault, VaultPauseScope, VaultRef};
 
-    use crate::ManagerError;
+    use crate::{
+        ManagerError, MarketInfo, MarketStats, MarketStatus, MarketTemplate, Portfolio, PortfolioPosition,
+        ProtocolStats,
+    };
+
+    const STORAGE_VERSION: u32 = 1;
+    // prefix pallet-contracts hashes together with the deployer, code hash, input and salt
+    const CONTRACT_ADDRESS_PREFIX: &[u8; 16] = b"contract_addr_v1";
 
     #[ink(storage)]
     pub struct Manager {
         version: u8,
-        owner: AccountId,
+        access_control: AccessControlData,
+        storage_version: u32,
         oracle: AccountId,
         wazero: AccountId,
         vault: AccountId,
-        markets: Vec<AccountId>,
+        // underlying asset => market metadata
+        market_info: Mapping<AccountId, MarketInfo>,
+        // underlying assets in the order their markets were deployed
+        underlying_assets: Vec<AccountId>,
+        // index of the next deployed market, part of its salt
         incremented_id: u128,
+        market_template: Lazy<MarketTemplate>,
     }
 
     impl Manager {
@@ -33,23 +56,22 @@ mod manager {
                 .endowment(0)
                 .code_hash(vault_hash)
                 .salt_bytes(
-                    &[
-                        version.to_le_bytes().as_ref(),
-                        Self::env().caller().as_ref(),
-                    ]
-                    .concat()[..4],
+                    Self::env().hash_encoded::<Blake2x256, _>(&(version, Self::env().caller())),
                 )
                 .instantiate()
                 .to_account_id();
 
             Self {
                 version,
-                owner: Self::env().caller(),
+                access_control: AccessControlData::new(Self::env().caller()),
+                storage_version: STORAGE_VERSION,
                 oracle,
                 wazero,
                 vault,
-                markets: Default::default(),
+                market_info: Default::default(),
+                underlying_assets: Default::default(),
                 incremented_id: 0,
+                market_template: Default::default(),
             }
         }
 
@@ -60,61 +82,397 @@ mod manager {
 
         #[ink(message)]
         pub fn view_markets(&self) -> Vec<AccountId> {
-            self.markets.clone()
+            self.underlying_assets
+                .iter()
+                .filter_map(|asset| self.market_info.get(asset))
+                .map(|info| info.market)
+                .collect()
         } 
 
         #[ink(message)]
-        pub fn add_collateral_asset(&mut self, asset: AccountId) -> Result<(), ManagerError> {
-            if self.env().caller() != self.owner {
-                return Err(ManagerError::NotOwner);
+        pub fn market_for_asset(&self, underlying_asset: AccountId) -> Option<MarketInfo> {
+            self.view_market_info(underlying_asset)
+        }
+
+        #[ink(message)]
+        pub fn markets_paginated(&self, offset: u32, limit: u32) -> Vec<MarketInfo> {
+            self.underlying_assets
+                .iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .filter_map(|asset| self.view_market_info(*asset))
+                .collect()
+        }
+
+        /// Positions of `user` across all registered markets, valued at current prices.
+        /// Markets that fail to value their positions are listed in `unavailable_markets`
+        /// instead of failing the whole view.
+        #[ink(message)]
+        pub fn view_user_portfolio(&self, user: AccountId) -> Result<Portfolio, ManagerError> {
+            let mut positions = Vec::new();
+            let mut margin_used: u128 = 0;
+            let mut collateral_value: u128 = 0;
+            let mut unrealised_pnl: i128 = 0;
+            let mut unavailable_markets = Vec::new();
+
+            for underlying_asset in self.underlying_assets.iter() {
+                let info = self
+                    .market_info
+                    .get(underlying_asset)
+                    .ok_or(ManagerError::MarketNotFound)?;
+
+                let market: MarketRef = FromAccountId::from_account_id(info.market);
+                let market_positions = match self.view_market_positions(&market, info.market, user) {
+                    Ok(market_positions) => market_positions,
+                    Err(_) => {
+                        unavailable_markets.push(info.market);
+                        continue;
+                    }
+                };
+
+                for portfolio_position in market_positions {
+                    margin_used = margin_used
+                        .checked_add(portfolio_position.collateral_usd)
+                        .ok_or(ManagerError::Overflow(String::from("view_user_portfolio_1")))?;
+                    collateral_value = collateral_value
+                        .checked_add(portfolio_position.collateral_value_usd)
+                        .ok_or(ManagerError::Overflow(String::from("view_user_portfolio_2")))?;
+                    unrealised_pnl = unrealised_pnl
+                        .checked_add(portfolio_position.pnl_usd)
+                        .ok_or(ManagerError::Overflow(String::from("view_user_portfolio_3")))?;
+
+                    positions.push(portfolio_position);
+                }
+            }
+
+            let equity = i128::try_from(collateral_value)
+                .map_err(|_| ManagerError::Overflow(String::from("view_user_portfolio_4")))?
+                .checked_add(unrealised_pnl)
+                .ok_or(ManagerError::Overflow(String::from("view_user_portfolio_5")))?;
+
+            Ok(Portfolio {
+                positions,
+                equity,
+                margin_used,
+                collateral_value,
+                unrealised_pnl,
+                unavailable_markets,
+            })
+        }
+
+        /// Pool value, open interest and LP supply of every registered market. Markets whose
+        /// pool can't be valued are listed in `unavailable_markets` instead of failing the view.
+        #[ink(message)]
+        pub fn view_protocol_stats(&self) -> Result<ProtocolStats, ManagerError> {
+            let mut stats = Vec::new();
+            let mut unavailable_markets = Vec::new();
+
+            for underlying_asset in self.underlying_assets.iter() {
+                let info = self
+                    .market_info
+                    .get(underlying_asset)
+                    .ok_or(ManagerError::MarketNotFound)?;
+
+                let market: MarketRef = FromAccountId::from_account_id(info.market);
+                let tvl = match market.view_pool_value() {
+                    Ok(tvl) => tvl,
+                    Err(_) => {
+                        unavailable_markets.push(info.market);
+                        continue;
+                    }
+                };
+                let (long_open_interest, short_open_interest) = market.view_open_interest();
+
+                let lp_token: contract_ref!(PSP22) = info.market.into();
+
+                stats.push(MarketStats {
+                    market: info.market,
+                    underlying_asset: *underlying_asset,
+                    tvl,
+                    long_open_interest,
+                    short_open_interest,
+                    lp_supply: lp_token.total_supply(),
+                });
+            }
+
+            Ok(ProtocolStats {
+                markets: stats,
+                unavailable_markets,
+            })
+        }
+
+        /// Pauses, resumes or delists the market of `underlying_asset`. Deprecated markets only
+        /// allow closing positions and withdrawing liquidity, and can't be reactivated.
+        #[ink(message)]
+        pub fn set_market_status(
+            &mut self,
+            underlying_asset: AccountId,
+            status: MarketStatus,
+        ) -> Result<(), ManagerError> {
+            match status {
+                MarketStatus::Deprecated => self.ensure_role(Role::Admin)?,
+                _ => self.ensure_role(Role::Guardian)?,
+            }
+
+            let mut info = self
+                .market_info
+                .get(underlying_asset)
+                .ok_or(ManagerError::MarketNotFound)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(info.market);
+            if info.status == MarketStatus::Deprecated || market.is_deprecated() {
+                return Err(ManagerError::MarketDeprecated);
+            }
+
+            // the market itself rejects opens and deposits once deprecated, so guardians can't
+            // revive it through `set_market_paused`
+            let paused_scopes: &[PauseScope] = match status {
+                MarketStatus::Active | MarketStatus::Deprecated => &[],
+                MarketStatus::Paused => &[
+                    PauseScope::Open,
+                    PauseScope::Close,
+                    PauseScope::Liquidation,
+                    PauseScope::Deposit,
+                    PauseScope::Withdraw,
+                ],
+            };
+
+            if status == MarketStatus::Deprecated {
+                market.deprecate()
+                    .map_err(|err| ManagerError::MarketError(err))?;
+            }
+
+            for scope in [
+                PauseScope::Open,
+                PauseScope::Close,
+                PauseScope::Liquidation,
+                PauseScope::Deposit,
+                PauseScope::Withdraw,
+            ] {
+                market.set_paused(scope, paused_scopes.contains(&scope))
+                    .map_err(|err| ManagerError::MarketError(err))?;
             }
 
+            info.status = status;
+            self.market_info.insert(underlying_asset, &info);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_collateral_asset(&mut self, asset: AccountId) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
             let mut vault: contract_ref!(CollateralVault) = self.vault.into();
             vault.add_asset(asset)
                 .map_err(|err| ManagerError::VaultError(err))       
         }
 
         #[ink(message)]
-        pub fn deploy_market(
+        pub fn set_market_oracle_pair(
             &mut self,
-            market_hash: Hash,
-            name: Option<String>,
-            symbol: Option<String>,
-            underlying_asset: AccountId,
-            liquidation_threshold: i8,
-            liquidation_penalty: u8,
-            protocol_fee: u8,
-        ) -> Result<AccountId, ManagerError> {
-            if self.env().caller() != self.owner {
-                return Err(ManagerError::NotOwner);
+            market: AccountId,
+            asset: AccountId,
+            pair: String,
+            decimals: u8,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(market);
+            market.set_oracle_pair(asset, pair, decimals)
+                .map_err(|err| ManagerError::MarketError(err))
+        }
+
+        #[ink(message)]
+        pub fn grant_market_role(
+            &mut self,
+            market: AccountId,
+            role: Role,
+            account: AccountId,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut market: contract_ref!(AccessControl) = market.into();
+            market.grant_role(role, account)
+                .map_err(ManagerError::AccessControlError)
+        }
+
+        #[ink(message)]
+        pub fn revoke_market_role(
+            &mut self,
+            market: AccountId,
+            role: Role,
+            account: AccountId,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut market: contract_ref!(AccessControl) = market.into();
+            market.revoke_role(role, account)
+                .map_err(ManagerError::AccessControlError)
+        }
+
+        #[ink(message)]
+        pub fn set_market_paused(
+            &mut self,
+            market: AccountId,
+            scope: PauseScope,
+            paused: bool,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Guardian)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(market);
+            market.set_paused(scope, paused)
+                .map_err(|err| ManagerError::MarketError(err))
+        }
+
+        #[ink(message)]
+        pub fn grant_vault_role(&mut self, role: Role, account: AccountId) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut vault: contract_ref!(AccessControl) = self.vault.into();
+            vault.grant_role(role, account)
+                .map_err(ManagerError::AccessControlError)
+        }
+
+        #[ink(message)]
+        pub fn revoke_vault_role(&mut self, role: Role, account: AccountId) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut vault: contract_ref!(AccessControl) = self.vault.into();
+            vault.revoke_role(role, account)
+                .map_err(ManagerError::AccessControlError)
+        }
+
+        #[ink(message)]
+        pub fn set_vault_paused(
+            &mut self,
+            scope: VaultPauseScope,
+            paused: bool,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Guardian)?;
+
+            let mut vault: VaultRef = FromAccountId::from_account_id(self.vault);
+            vault.set_paused(scope, paused)
+                .map_err(|err| ManagerError::VaultError(err))
+        }
+
+        #[ink(message)]
+        pub fn queue_market_config_change(
+            &mut self,
+            market: AccountId,
+            change: ConfigChange,
+        ) -> Result<u128, ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(market);
+            market.queue_config_change(change)
+                .map_err(|err| ManagerError::MarketError(err))
+        }
+
+        #[ink(message)]
+        pub fn execute_market_config_change(
+            &mut self,
+            market: AccountId,
+            id: u128,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(market);
+            market.execute_config_change(id)
+                .map_err(|err| ManagerError::MarketError(err))
+        }
+
+        #[ink(message)]
+        pub fn cancel_market_config_change(
+            &mut self,
+            market: AccountId,
+            id: u128,
+        ) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(market);
+            market.cancel_config_change(id)
+                .map_err(|err| ManagerError::MarketError(err))
+        }
+
+        #[ink(message)]
+        pub fn claim_market_protocol_fees(
+            &mut self,
+            market: AccountId,
+            asset: AccountId,
+            to: AccountId,
+        ) -> Result<u128, ManagerError> {
+            self.ensure_role(Role::FeeCollector)?;
+
+            let mut market: MarketRef = FromAccountId::from_account_id(market);
+            market.claim_protocol_fees(asset, to)
+                .map_err(|err| ManagerError::MarketError(err))
+        }
+
+        #[ink(message)]
+        pub fn view_market_template(&self) -> Option<MarketTemplate> {
+            self.market_template.get()
+        }
+
+        /// Sets the code and parameters used by `deploy_market`. Markets deployed earlier keep
+        /// theirs, their parameters change through the market's config timelock.
+        #[ink(message)]
+        pub fn set_market_template(&mut self, template: MarketTemplate) -> Result<(), ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.market_template.set(&template);
+
+            Ok(())
+        }
+
+        /// Deploys the market of `underlying_asset` from the market template. The LP token
+        /// takes the name and symbol of the underlying asset.
+        #[ink(message)]
+        pub fn deploy_market(&mut self, underlying_asset: AccountId) -> Result<AccountId, ManagerError> {
+            self.ensure_role(Role::Admin)?;
+
+            if self.market_info.contains(underlying_asset) {
+                return Err(ManagerError::MarketAlreadyExists);
             }
 
+            let template = self.market_template
+                .get()
+                .ok_or(ManagerError::MarketTemplateNotSet)?;
+
             let asset: contract_ref!(PSP22Metadata) = underlying_asset.into();
+            let symbol = asset.token_symbol();
             let market = MarketRef::new(
-                    name,
-                    symbol,
+                    asset.token_name(),
+                    symbol.clone(),
                     asset.token_decimals(),
                     underlying_asset,
                     self.oracle,
                     self.vault,
                     self.wazero,
-                    liquidation_threshold,
-                    liquidation_penalty,
-                    protocol_fee,
+                    template.liquidation_threshold,
+                    template.liquidation_penalty,
+                    template.protocol_fee,
                 )
                 .endowment(0)
-                .code_hash(market_hash)
-                .salt_bytes(
-                    &[
-                        self.version.to_le_bytes().as_ref(),
-                        Self::env().caller().as_ref(),
-                    ]
-                    .concat()[..4],
-                )
+                .code_hash(template.code_hash)
+                .salt_bytes(self.market_salt(underlying_asset))
                 .instantiate()
                 .to_account_id();
 
-            self.markets.push(market);
+            let market_ref: MarketRef = FromAccountId::from_account_id(market);
+            self.market_info.insert(
+                underlying_asset,
+                &MarketInfo {
+                    market,
+                    underlying_asset,
+                    symbol,
+                    created_at_block: self.env().block_number(),
+                    status: MarketStatus::Active,
+                    config: market_ref.view_market_config(),
+                },
+            );
+            self.underlying_assets.push(underlying_asset);
+            self.incremented_id += 1;
 
             let mut vault: contract_ref!(CollateralVault) = self.vault.into();
             vault.add_market(market)
@@ -122,5 +480,314 @@ mod manager {
 
             Ok(market)
         }
+
+        /// Returns the address `deploy_market` deploys the market of `underlying_asset` to
+        /// when it is the next market deployed with the current template.
+        #[ink(message)]
+        pub fn predict_market_address(&self, underlying_asset: AccountId) -> Result<AccountId, ManagerError> {
+            let template = self.market_template
+                .get()
+                .ok_or(ManagerError::MarketTemplateNotSet)?;
+
+            let asset: contract_ref!(PSP22Metadata) = underlying_asset.into();
+            let params = MarketRef::new(
+                    asset.token_name(),
+                    asset.token_symbol(),
+                    asset.token_decimals(),
+                    underlying_asset,
+                    self.oracle,
+                    self.vault,
+                    self.wazero,
+                    template.liquidation_threshold,
+                    template.liquidation_penalty,
+                    template.protocol_fee,
+                )
+                .endowment(0)
+                .code_hash(template.code_hash)
+                .salt_bytes(self.market_salt(underlying_asset))
+                .params();
+
+            Ok(AccountId::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                CONTRACT_ADDRESS_PREFIX,
+                self.env().account_id(),
+                params.code_hash(),
+                params.exec_input().encode(),
+                params.salt_bytes().as_slice(),
+            ))))
+        }
+
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: Hash) -> Result<(), ManagerError> {
+            self.ensure_owner()?;
+
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| ManagerError::UpgradeFailed)?;
+
+            self.emit_event(Upgraded { code_hash });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn view_storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<(), ManagerError> {
+            self.ensure_owner()?;
+
+            let from_version = self.storage_version;
+            if from_version >= STORAGE_VERSION {
+                return Ok(());
+            }
+
+            self.storage_version = STORAGE_VERSION;
+
+            self.emit_event(Migrated {
+                from_version,
+                to_version: STORAGE_VERSION,
+            });
+
+            Ok(())
+        }
+
+        /// Upgrades every deployed market to `code_hash` and migrates its storage.
+        #[ink(message)]
+        pub fn upgrade_markets(&mut self, code_hash: Hash) -> Result<(), ManagerError> {
+            self.ensure_owner()?;
+
+            for market in self.view_markets() {
+                let mut market: MarketRef = FromAccountId::from_account_id(market);
+                market.upgrade(code_hash)
+                    .map_err(|err| ManagerError::MarketError(err))?;
+                market.migrate()
+                    .map_err(|err| ManagerError::MarketError(err))?;
+            }
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn upgrade_vault(&mut self, code_hash: Hash) -> Result<(), ManagerError> {
+            self.ensure_owner()?;
+
+            let mut vault: VaultRef = FromAccountId::from_account_id(self.vault);
+            vault.upgrade(code_hash)
+                .map_err(|err| ManagerError::VaultError(err))?;
+            vault.migrate()
+                .map_err(|err| ManagerError::VaultError(err))
+        }
+
+        fn emit_event<E>(&self, event: E)
+        where
+            E: Into<<Manager as ContractEventBase>::Type>,
+        {
+            EmitEvent::<Manager>::emit_event(self.env(), event);
+        }
+
+        fn emit_access_control_events(&self, events: Vec<AccessControlEvent>) {
+            for event in events {
+                match event {
+                    AccessControlEvent::OwnershipTransferStarted {
+                        previous_owner,
+                        new_owner,
+                    } => self.emit_event(OwnershipTransferStarted {
+                        previous_owner,
+                        new_owner,
+                    }),
+                    AccessControlEvent::OwnershipTransferred {
+                        previous_owner,
+                        new_owner,
+                    } => self.emit_event(OwnershipTransferred {
+                        previous_owner,
+                        new_owner,
+                    }),
+                    AccessControlEvent::RoleGranted {
+                        role,
+                        account,
+                        sender,
+                    } => self.emit_event(RoleGranted {
+                        role,
+                        account,
+                        sender,
+                    }),
+                    AccessControlEvent::RoleRevoked {
+                        role,
+                        account,
+                        sender,
+                    } => self.emit_event(RoleRevoked {
+                        role,
+                        account,
+                        sender,
+                    }),
+                }
+            }
+        }
+
+        // risk parameters can change through the market's config timelock, so they are
+        // read from the market instead of the deploy time snapshot
+        fn view_market_info(&self, underlying_asset: AccountId) -> Option<MarketInfo> {
+            let mut info = self.market_info.get(underlying_asset)?;
+            let market: MarketRef = FromAccountId::from_account_id(info.market);
+            info.config = market.view_market_config();
+            if market.is_deprecated() {
+                info.status = MarketStatus::Deprecated;
+            }
+
+            Some(info)
+        }
+
+        fn view_market_positions(
+            &self,
+            market: &MarketRef,
+            market_id: AccountId,
+            user: AccountId,
+        ) -> Result<Vec<PortfolioPosition>, MarketError> {
+            let mut positions = Vec::new();
+
+            for (position, pnl_usd, _, _, _) in market.view_all(user)? {
+                let collateral_value_usd = market.view_collateral_value(user, position.id)?;
+
+                positions.push(PortfolioPosition {
+                    market: market_id,
+                    pnl_usd,
+                    collateral_usd: position.collateral_usd,
+                    collateral_value_usd,
+                    liquidation_price: position.liquidation_price,
+                    position,
+                });
+            }
+
+            Ok(positions)
+        }
+
+        fn market_salt(&self, underlying_asset: AccountId) -> [u8; 32] {
+            self.env()
+                .hash_encoded::<Blake2x256, _>(&(self.version, underlying_asset, self.incremented_id))
+        }
+
+        fn ensure_owner(&self) -> Result<(), ManagerError> {
+            self.access_control
+                .ensure_owner(self.env().caller())
+                .map_err(ManagerError::AccessControlError)
+        }
+
+        fn ensure_role(&self, role: Role) -> Result<(), ManagerError> {
+            self.access_control
+                .ensure_role(self.env().caller(), role)
+                .map_err(ManagerError::AccessControlError)
+        }
+    }
+
+    #[ink(event)]
+    pub struct Upgraded {
+        code_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct Migrated {
+        from_version: u32,
+        to_version: u32,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferStarted {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleGranted {
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    impl AccessControl for Manager {
+        #[ink(message)]
+        fn owner(&self) -> AccountId {
+            self.access_control.owner()
+        }
+
+        #[ink(message)]
+        fn pending_owner(&self) -> Option<AccountId> {
+            self.access_control.pending_owner()
+        }
+
+        #[ink(message)]
+        fn has_role(&self, role: Role, account: AccountId) -> bool {
+            self.access_control.has_role(role, account)
+        }
+
+        #[ink(message)]
+        fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .transfer_ownership(self.env().caller(), new_owner)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn accept_ownership(&mut self) -> Result<(), AccessControlError> {
+            let events = self.access_control.accept_ownership(self.env().caller())?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn renounce_ownership(&mut self) -> Result<(), AccessControlError> {
+            let events = self.access_control.renounce_ownership(self.env().caller())?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn grant_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .grant_role(self.env().caller(), role, account)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .revoke_role(self.env().caller(), role, account)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn renounce_role(&mut self, role: Role) -> Result<(), AccessControlError> {
+            let events = self.access_control.renounce_role(self.env().caller(), role);
+            self.emit_access_control_events(events);
+            Ok(())
+        }
     }
 }
@@ -0,0 +1,34 @@
+use ink::{
+    prelude::string::String,
+    primitives::AccountId,
+};
+use market::MarketConfig;
+use scale::{Decode, Encode};
+
+#[derive(Decode, Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum MarketStatus {
+    Active,
+    Paused,
+    // delisted, only closes, liquidations and LP withdrawals are allowed
+    Deprecated,
+}
+
+#[derive(Decode, Encode, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct MarketInfo {
+    pub market: AccountId,
+    pub underlying_asset: AccountId,
+    pub symbol: Option<String>,
+    pub created_at_block: u32,
+    pub status: MarketStatus,
+    pub config: MarketConfig,
+}
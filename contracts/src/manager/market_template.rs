@@ -0,0 +1,17 @@
+use ink::primitives::Hash;
+use scale::{Decode, Encode};
+
+// code and constructor arguments every market is deployed with, so its address only depends
+// on the underlying asset
+#[derive(Decode, Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct MarketTemplate {
+    pub code_hash: Hash,
+    pub liquidation_threshold: i32,
+    pub liquidation_penalty: u16,
+    pub protocol_fee: u16,
+}
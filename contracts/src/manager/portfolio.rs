@@ -0,0 +1,57 @@
+use ink::{prelude::vec::Vec, primitives::AccountId};
+use market::Position;
+use scale::{Decode, Encode};
+
+#[derive(Decode, Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+#[derive(Debug)]
+pub struct PortfolioPosition {
+    pub market: AccountId,
+    pub position: Position,
+    // net of funding and borrow fees, capped at the max profit, USD with 6 decimals
+    pub pnl_usd: i128,
+    // collateral valued at entry
+    pub collateral_usd: u128,
+    // collateral valued at the current price of the collateral asset
+    pub collateral_value_usd: u128,
+    pub liquidation_price: u128,
+}
+
+// USD with 6 decimals
+#[derive(Decode, Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+#[derive(Debug)]
+pub struct Portfolio {
+    pub positions: Vec<PortfolioPosition>,
+    // current collateral value plus unrealised PnL
+    pub equity: i128,
+    // collateral valued at entry
+    pub margin_used: u128,
+    pub collateral_value: u128,
+    pub unrealised_pnl: i128,
+    // markets whose positions couldn't be valued, e.g. on a stale oracle, and are left out
+    pub unavailable_markets: Vec<AccountId>,
+}
+
+#[derive(Decode, Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+#[derive(Debug, PartialEq, Eq)]
+pub struct MarketStats {
+    pub market: AccountId,
+    pub underlying_asset: AccountId,
+    // pool value in the underlying asset, net of traders' unrealised PnL
+    pub tvl: u128,
+    // USD with 6 decimals
+    pub long_open_interest: u128,
+    pub short_open_interest: u128,
+    pub lp_supply: u128,
+}
+
+#[derive(Decode, Encode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+#[derive(Debug, PartialEq, Eq)]
+pub struct ProtocolStats {
+    pub markets: Vec<MarketStats>,
+    // markets whose pool couldn't be valued, e.g. on a stale oracle, and are left out
+    pub unavailable_markets: Vec<AccountId>,
+}
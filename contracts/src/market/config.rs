@@ -0,0 +1,55 @@
+use ink::primitives::AccountId;
+use scale::{Decode, Encode};
+
+#[derive(Decode, Encode, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct MarketConfig {
+    pub min_leverage: u32,
+    pub max_leverage: u32,
+    pub min_collateral_usd: u128,
+    pub max_position_notional: u128,
+    pub liquidation_threshold: i32,
+    pub liquidation_penalty: u16,
+    pub protocol_fee: u16,
+}
+
+#[derive(Decode, Encode, Clone)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfigChange {
+    LiquidationThreshold(i32),
+    LiquidationPenalty(u16),
+    ProtocolFee(u16),
+    Oracle(AccountId),
+    Vault(AccountId),
+    Timelock(u32),
+    // max price age in seconds, max price deviation in basis points; zero disables either check
+    OracleGuards(u64, u16),
+    // open fee, close fee, hourly borrow fee, protocol fee share, all in basis points
+    Fees(u16, u16, u16, u16),
+    // min leverage, max leverage, min collateral in USD, max position notional in USD
+    PositionLimits(u32, u32, u128, u128),
+    MaxProfitMultiple(u8),
+    // basis points
+    MaxUtilisation(u16),
+    FundingRateFactor(u128),
+}
+
+#[derive(Decode, Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug)]
+pub struct PendingConfigChange {
+    pub id: u128,
+    pub change: ConfigChange,
+    pub eta: u32,
+}
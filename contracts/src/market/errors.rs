@@ -1,3 +1,4 @@
+use access_control::AccessControlError;
 use ink::prelude::string::String;
 use psp22::PSP22Error;
 use vault::VaultError;
@@ -18,5 +19,34 @@ pub enum MarketError {
     TransferFailed,
     OracleFailed,
     VaultError(VaultError),
-    PSP22Error(PSP22Error)
+    PSP22Error(PSP22Error),
+    // variants added after the first deployment are appended, so encoded indexes stay stable
+    AccessControlError(AccessControlError),
+    CollateralTooLow,
+    ConfigChangeNotFound,
+    ConfigChangeNotReady,
+    Deprecated,
+    InsufficientLiquidity,
+    InsufficientMargin,
+    InvalidAmount,
+    InvalidLeverage,
+    InvalidTriggerPrice,
+    LeverageTooHigh,
+    LeverageTooLow,
+    OpenInterestCapExceeded,
+    OracleNotConfigured,
+    OrderExpired,
+    OrderNotFound,
+    OrderNotTriggered,
+    OrdersOpen,
+    Paused,
+    PositionsOpen,
+    PositionTooLarge,
+    PriceDeviationTooHigh,
+    StalePrice,
+    TriggerNotReached,
+    UpgradeFailed,
+    UtilisationExceeded,
+    WithdrawLocked,
+    WithdrawRequestNotFound,
 }
@@ -1,51 +1,170 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
+mod config;
 mod errors;
+mod order;
+mod pause;
 mod position;
+mod withdraw_request;
 
+pub use config::{ConfigChange, MarketConfig, PendingConfigChange};
 pub use errors::MarketError;
-pub use position::Position;
+pub use order::Order;
+pub use pause::PauseScope;
+pub use position::{Position, VersionedPosition};
+pub use withdraw_request::WithdrawRequest;
 
 pub use self::market::MarketRef;
 
 #[ink::contract]
 pub mod market {
-    use crate::{MarketError, Position};
+    use access_control::{AccessControl, AccessControlData, AccessControlError, AccessControlEvent, Role};
+    use crate::{
+        ConfigChange, MarketConfig, MarketError, Order, PauseScope, PendingConfigChange, Position,
+        VersionedPosition, WithdrawRequest,
+    };
     use dia_oracle_getter::OracleGetters;
     use ink::{
+        codegen::EmitEvent,
         contract_ref,
         env::{
             call::{build_call, ExecutionInput, Selector},
             DefaultEnvironment,
         },
         prelude::{format, string::String, vec::Vec},
-        storage::Mapping
+        reflect::ContractEventBase,
+        storage::{Lazy, Mapping}
     };
-    use psp22::{PSP22Data, PSP22Error, PSP22Metadata, PSP22};
+    use psp22::{PSP22Data, PSP22Error, PSP22Event, PSP22Metadata, PSP22};
     use vault::CollateralVault;
     use wrapped_azero::{WrappedAZERO, WAZERO_DEPOSIT_SELECTOR};
 
+    // funding indices and rates are scaled by 1e12
+    const FUNDING_PRECISION: i128 = 1_000_000_000_000;
+    // ~0.01% per hour at full skew with 1s blocks
+    const DEFAULT_FUNDING_RATE_FACTOR: u128 = 28_000;
+    const DEFAULT_TRIGGER_FEE: u16 = 100;
+    // position sizes in underlying units are scaled by 1e12 when averaging entry prices
+    const SIZE_PRECISION: u128 = 1_000_000_000_000;
+    // ~1 hour with 1s blocks
+    const DEFAULT_WITHDRAW_DELAY: u32 = 3_600;
+    const DEFAULT_MAX_UTILISATION: u16 = 8_000;
+    const DEFAULT_MAX_PROFIT_MULTIPLE: u8 = 9;
+    const BASIS_POINTS: u128 = 10_000;
+    // 1s blocks
+    const BLOCKS_PER_HOUR: u128 = 3_600;
+    const DEFAULT_OPEN_FEE: u16 = 10;
+    const DEFAULT_CLOSE_FEE: u16 = 10;
+    const DEFAULT_BORROW_FEE: u16 = 1;
+    const DEFAULT_PROTOCOL_FEE_SHARE: u16 = 2_000;
+    // leverage is in basis points, 10_000 = 1x
+    const DEFAULT_MIN_LEVERAGE: u32 = 10_000;
+    const DEFAULT_MAX_LEVERAGE: u32 = 500_000;
+    // ~1 day with 1s blocks
+    const DEFAULT_CONFIG_TIMELOCK: u32 = 86_400;
+    const STORAGE_VERSION: u32 = 1;
+
+    // (position, net pnl in USD, roe in basis points, market price, max profit in USD)
+    pub type PositionView = (Position, i128, i128, u128, u128);
+
+    // Fields added after a deployment go behind `Lazy` or `Mapping`, so the root struct
+    // written by older code still decodes after `upgrade`.
     #[ink(storage)]
     pub struct Market {
         data: PSP22Data,
         name: Option<String>,
         symbol: Option<String>,
         decimals: u8,
-        owner: AccountId,
+        access_control: AccessControlData,
+        storage_version: u32,
+        paused_scopes: Vec<PauseScope>,
         // (user, positionId) => Position
-        positions: Mapping<(AccountId, u128), Position>,
+        positions: Mapping<(AccountId, u128), VersionedPosition>,
         // AccountId => positionId
         ids_per_user: Mapping<AccountId, Vec<u128>>,
         // user => latest position id
         new_id: Mapping<AccountId, u128>,
+        // (user, positionId) => pending limit order
+        orders: Mapping<(AccountId, u128), Order>,
+        // AccountId => pending order ids
+        order_ids_per_user: Mapping<AccountId, Vec<u128>>,
         // tradable asset
         underlying_asset: AccountId,
         oracle: AccountId,
         vault: AccountId,
         wazero: AccountId,
-        liquidation_threshold: i8,
-        liquidation_penalty: u8,
-        protocol_fee: u8,
+        // basis points of pnl at which a position is liquidated, e.g. -6000
+        liquidation_threshold: i32,
+        // basis points of the leftover collateral seized on liquidation
+        liquidation_penalty: u16,
+        // basis points of the seized collateral kept by the protocol
+        protocol_fee: u16,
+        // cumulative funding paid per unit of long notional
+        funding_index: i128,
+        last_funding_block: u32,
+        // funding rate per block when open interest is fully one-sided
+        funding_rate_factor: u128,
+        // basis points of returned collateral paid to keepers executing take-profit/stop-loss
+        trigger_fee: u16,
+        // aggregate USD notional of open positions
+        long_open_interest: u128,
+        short_open_interest: u128,
+        // aggregate position size in underlying units, scaled by SIZE_PRECISION
+        long_open_size: u128,
+        short_open_size: u128,
+        max_long_open_interest: u128,
+        max_short_open_interest: u128,
+        // underlying set aside to cover the maximum payout of open positions
+        reserved_amount: u128,
+        // basis points of the underlying balance that positions may reserve
+        max_utilisation: u16,
+        // profit paid on close is capped at this multiple of the position collateral
+        max_profit_multiple: u8,
+        // basis points of notional charged when opening and closing
+        open_fee: u16,
+        close_fee: u16,
+        // basis points of a position's reserve, the smaller of notional and max profit, charged per hour
+        borrow_fee: u16,
+        // cumulative borrow fee in basis points times blocks
+        borrow_index: u128,
+        // basis points of trading fees accrued to the owner, the rest stays with LPs
+        protocol_fee_share: u16,
+        // asset => fees held by the market on behalf of the owner
+        protocol_fees: Mapping<AccountId, u128>,
+        min_leverage: u32,
+        max_leverage: u32,
+        // USD with 6 decimals
+        min_collateral_usd: u128,
+        max_position_notional: u128,
+        // blocks between queueing and applying a config change
+        config_timelock: u32,
+        // changeId => queued config change
+        config_changes: Mapping<u128, PendingConfigChange>,
+        config_change_ids: Vec<u128>,
+        next_config_change_id: u128,
+        // seconds, 0 disables the staleness check
+        max_price_age: u64,
+        // basis points, 0 disables the circuit breaker
+        max_price_deviation: u16,
+        // asset => last price accepted by a state-changing call
+        last_prices: Mapping<AccountId, u128>,
+        // asset => (oracle pair, oracle decimals)
+        oracle_pairs: Mapping<AccountId, (String, u8)>,
+        symbol_fallback: bool,
+        // (user, requestId) => LP tokens locked for redemption
+        withdraw_requests: Mapping<(AccountId, u128), WithdrawRequest>,
+        // AccountId => pending withdraw request ids
+        withdraw_ids_per_user: Mapping<AccountId, Vec<u128>>,
+        // user => latest withdraw request id
+        new_withdraw_id: Mapping<AccountId, u128>,
+        // blocks between a withdraw request and its claim
+        withdraw_delay: u32,
+        // blocks, 0 disables epochs; otherwise claims open at the next epoch boundary
+        withdraw_epoch_length: u32,
+        // set once the market is wound down, new positions and deposits are rejected for good
+        deprecated: Lazy<bool>,
+        // pending orders, their collateral is escrowed in the vault
+        open_orders: Lazy<u32>,
     }
 
     impl Market {
@@ -56,10 +175,14 @@ pub mod market {
                 name: Default::default(),
                 symbol: Default::default(),
                 decimals: Default::default(),
-                owner: Self::env().caller(),
+                access_control: AccessControlData::new(Self::env().caller()),
+                storage_version: STORAGE_VERSION,
+                paused_scopes: Default::default(),
                 positions: Default::default(),
                 ids_per_user: Default::default(),
                 new_id: Default::default(),
+                orders: Default::default(),
+                order_ids_per_user: Default::default(),
                 underlying_asset: AccountId::from([0; 32]),
                 oracle: AccountId::from([0; 32]),
                 vault: AccountId::from([0; 32]),
@@ -67,6 +190,45 @@ pub mod market {
                 liquidation_threshold: Default::default(),
                 liquidation_penalty: Default::default(),
                 protocol_fee: Default::default(),
+                funding_index: 0,
+                last_funding_block: Self::env().block_number(),
+                funding_rate_factor: DEFAULT_FUNDING_RATE_FACTOR,
+                trigger_fee: DEFAULT_TRIGGER_FEE,
+                long_open_interest: 0,
+                short_open_interest: 0,
+                long_open_size: 0,
+                short_open_size: 0,
+                max_long_open_interest: u128::MAX,
+                max_short_open_interest: u128::MAX,
+                reserved_amount: 0,
+                max_utilisation: DEFAULT_MAX_UTILISATION,
+                max_profit_multiple: DEFAULT_MAX_PROFIT_MULTIPLE,
+                open_fee: DEFAULT_OPEN_FEE,
+                close_fee: DEFAULT_CLOSE_FEE,
+                borrow_fee: DEFAULT_BORROW_FEE,
+                borrow_index: 0,
+                protocol_fee_share: DEFAULT_PROTOCOL_FEE_SHARE,
+                protocol_fees: Default::default(),
+                min_leverage: DEFAULT_MIN_LEVERAGE,
+                max_leverage: DEFAULT_MAX_LEVERAGE,
+                min_collateral_usd: 0,
+                max_position_notional: u128::MAX,
+                config_timelock: DEFAULT_CONFIG_TIMELOCK,
+                config_changes: Default::default(),
+                config_change_ids: Default::default(),
+                next_config_change_id: 0,
+                max_price_age: 0,
+                max_price_deviation: 0,
+                last_prices: Default::default(),
+                oracle_pairs: Default::default(),
+                symbol_fallback: false,
+                withdraw_requests: Default::default(),
+                withdraw_ids_per_user: Default::default(),
+                new_withdraw_id: Default::default(),
+                withdraw_delay: DEFAULT_WITHDRAW_DELAY,
+                withdraw_epoch_length: 0,
+                deprecated: Default::default(),
+                open_orders: Default::default(),
             }
         }
 
@@ -79,19 +241,23 @@ pub mod market {
             oracle: AccountId,
             vault: AccountId,
             wazero: AccountId,
-            liquidation_threshold: i8,
-            liquidation_penalty: u8,
-            protocol_fee: u8,
+            liquidation_threshold: i32,
+            liquidation_penalty: u16,
+            protocol_fee: u16,
         ) -> Self {
             Self {
                 data: PSP22Data::new(0, Self::env().caller()),
                 name,
                 symbol,
                 decimals,
-                owner: Self::env().caller(),
+                access_control: AccessControlData::new(Self::env().caller()),
+                storage_version: STORAGE_VERSION,
+                paused_scopes: Default::default(),
                 positions: Default::default(),
                 ids_per_user: Default::default(),
                 new_id: Default::default(),
+                orders: Default::default(),
+                order_ids_per_user: Default::default(),
                 underlying_asset,
                 oracle,
                 vault,
@@ -99,6 +265,45 @@ pub mod market {
                 liquidation_threshold,
                 liquidation_penalty,
                 protocol_fee,
+                funding_index: 0,
+                last_funding_block: Self::env().block_number(),
+                funding_rate_factor: DEFAULT_FUNDING_RATE_FACTOR,
+                trigger_fee: DEFAULT_TRIGGER_FEE,
+                long_open_interest: 0,
+                short_open_interest: 0,
+                long_open_size: 0,
+                short_open_size: 0,
+                max_long_open_interest: u128::MAX,
+                max_short_open_interest: u128::MAX,
+                reserved_amount: 0,
+                max_utilisation: DEFAULT_MAX_UTILISATION,
+                max_profit_multiple: DEFAULT_MAX_PROFIT_MULTIPLE,
+                open_fee: DEFAULT_OPEN_FEE,
+                close_fee: DEFAULT_CLOSE_FEE,
+                borrow_fee: DEFAULT_BORROW_FEE,
+                borrow_index: 0,
+                protocol_fee_share: DEFAULT_PROTOCOL_FEE_SHARE,
+                protocol_fees: Default::default(),
+                min_leverage: DEFAULT_MIN_LEVERAGE,
+                max_leverage: DEFAULT_MAX_LEVERAGE,
+                min_collateral_usd: 0,
+                max_position_notional: u128::MAX,
+                config_timelock: DEFAULT_CONFIG_TIMELOCK,
+                config_changes: Default::default(),
+                config_change_ids: Default::default(),
+                next_config_change_id: 0,
+                max_price_age: 0,
+                max_price_deviation: 0,
+                last_prices: Default::default(),
+                oracle_pairs: Default::default(),
+                symbol_fallback: false,
+                withdraw_requests: Default::default(),
+                withdraw_ids_per_user: Default::default(),
+                new_withdraw_id: Default::default(),
+                withdraw_delay: DEFAULT_WITHDRAW_DELAY,
+                withdraw_epoch_length: 0,
+                deprecated: Default::default(),
+                open_orders: Default::default(),
             }
         }
 
@@ -118,7 +323,7 @@ pub mod market {
 
         #[ink(message)]
         pub fn view_position(&self, user: AccountId, id: u128) -> Option<Position> {
-            self.positions.get((user, id))
+            self.get_position(user, id)
         }
 
         #[ink(message)]
@@ -127,7 +332,7 @@ pub mod market {
             let mut positions = Vec::new();
 
             for id in ids_for_user {
-                if let Some(position) = self.positions.get((user, id)) {
+                if let Some(position) = self.get_position(user, id) {
                     positions.push(position);
                 }
             }
@@ -137,321 +342,2193 @@ pub mod market {
 
         #[ink(message)]
         pub fn view_market_price(&self) -> Result<u128, MarketError> {
-            let metadata: contract_ref!(PSP22Metadata) = self.underlying_asset.into();
-            let symbol = metadata.token_symbol().ok_or(MarketError::OracleFailed)?;
-            self.get_price(symbol)
+            self.get_price(self.underlying_asset)
         }
 
         #[ink(message)]
-        pub fn get_price(&self, symbol: String) -> Result<u128, MarketError> {
-            let unwrapped_symbol = &symbol[1..];
-            let pair_symbol = format!("{unwrapped_symbol}/USD");
+        pub fn get_price(&self, asset: AccountId) -> Result<u128, MarketError> {
+            let (pair_symbol, oracle_decimals) = self.get_oracle_pair(asset)?;
 
             let oracle_getter: contract_ref!(OracleGetters) = self.oracle.into();
-            // DIA price oracle returns USD price with 18 decimals by default
-            let oracle_decimals: u8 = 18;
             let target_decimals: u8 = 6;
 
-            let (_timestamp, price) = oracle_getter
+            let (timestamp, price) = oracle_getter
                 .get_latest_price(pair_symbol)
                 .ok_or(MarketError::OracleFailed)?;
 
-            let abbreviated_price = price
-                .checked_div(
-                    10u128.checked_pow(oracle_decimals as u32 - target_decimals as u32)
-                        .ok_or(MarketError::Overflow(String::from("get_price")))?
-                )
-                .ok_or(MarketError::Overflow(String::from("get_price")));
+            // DIA timestamps are in seconds, block timestamps in milliseconds
+            let price_age = (self.env().block_timestamp() / 1000).saturating_sub(timestamp);
+            if self.max_price_age > 0 && price_age > self.max_price_age {
+                return Err(MarketError::StalePrice);
+            }
+
+            let abbreviated_price = if oracle_decimals >= target_decimals {
+                price
+                    .checked_div(
+                        10u128.checked_pow(oracle_decimals as u32 - target_decimals as u32)
+                            .ok_or(MarketError::Overflow(String::from("get_price")))?
+                    )
+                    .ok_or(MarketError::Overflow(String::from("get_price")))?
+            } else {
+                price
+                    .checked_mul(
+                        10u128.checked_pow(target_decimals as u32 - oracle_decimals as u32)
+                            .ok_or(MarketError::Overflow(String::from("get_price")))?
+                    )
+                    .ok_or(MarketError::Overflow(String::from("get_price")))?
+            };
+
+            if let Some(last_price) = self.last_prices.get(asset) {
+                if self.max_price_deviation > 0 && last_price > 0 {
+                    let deviation = abbreviated_price
+                        .abs_diff(last_price)
+                        .checked_mul(BASIS_POINTS)
+                        .ok_or(MarketError::Overflow(String::from("get_price_deviation")))?
+                        .checked_div(last_price)
+                        .ok_or(MarketError::Overflow(String::from("get_price_deviation")))?;
+
+                    if deviation > self.max_price_deviation as u128 {
+                        return Err(MarketError::PriceDeviationTooHigh);
+                    }
+                }
+            }
+
+            Ok(abbreviated_price)
+        }
+
+        #[ink(message)]
+        pub fn view_oracle_guards(&self) -> (u64, u16) {
+            (self.max_price_age, self.max_price_deviation)
+        }
+
+        /// Forgets the last accepted price for `asset` so the circuit breaker can be
+        /// released after a legitimate large move.
+        #[ink(message)]
+        pub fn reset_last_price(&mut self, asset: AccountId) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.last_prices.remove(asset);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn view_oracle_pair(&self, asset: AccountId) -> Option<(String, u8)> {
+            self.oracle_pairs.get(asset)
+        }
+
+        /// Registers the DIA pair (e.g. "AZERO/USD") and price decimals used to price `asset`.
+        #[ink(message)]
+        pub fn set_oracle_pair(
+            &mut self,
+            asset: AccountId,
+            pair: String,
+            decimals: u8,
+        ) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.oracle_pairs.insert(asset, &(pair, decimals));
 
-            abbreviated_price
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_oracle_pair(&mut self, asset: AccountId) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.oracle_pairs.remove(asset);
+
+            Ok(())
+        }
+
+        /// Enables deriving pairs from token symbols for assets without a registered pair.
+        #[ink(message)]
+        pub fn set_symbol_fallback(&mut self, enabled: bool) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.symbol_fallback = enabled;
+
+            Ok(())
+        }
+
+        fn fetch_price(&mut self, asset: AccountId) -> Result<u128, MarketError> {
+            let price = self.get_price(asset)?;
+            self.last_prices.insert(asset, &price);
+
+            Ok(price)
         }
 
         #[ink(message)]
         pub fn view_liquidation_price(
             &self,
-            asset: AccountId,
-            leverage: u8,
+            leverage: u32,
             is_long: bool,
         ) -> Result<u128, MarketError> {
-            let metadata: contract_ref!(PSP22Metadata) = asset.into();
-            let symbol = metadata.token_symbol().ok_or(MarketError::OracleFailed)?;
-            let entry_price = self.get_price(symbol)?;
+            let entry_price = self.get_price(self.underlying_asset)?;
             self.calculate_liquidation_price(entry_price, leverage, is_long)
         }
 
+        /// PnL net of funding and borrow fees in USD, and the matching ROE in basis points.
         #[ink(message)]
         pub fn view_position_pnl(
             &self,
             user: AccountId,
             id: u128,
-        ) -> Result<i128, MarketError> {
-            let position = self.positions
-                .get((user, id))
+        ) -> Result<(i128, i128), MarketError> {
+            let position = self.get_position(user, id)
                 .ok_or(MarketError::PositionNotFound)?;
 
             let new_price = self.view_market_price()?;
 
-            self.calculate_pnl_percent(position.entry_price, new_price, position.leverage, position.is_long)
+            let pnl_usd = self.calculate_net_pnl_usd(&position, new_price)?;
+            let roe = self.calculate_roe(pnl_usd, position.collateral_usd)?;
+
+            Ok((pnl_usd, roe))
+        }
+
+        /// Current USD value of the collateral backing a position, unlike `collateral_usd`
+        /// which is fixed at entry.
+        #[ink(message)]
+        pub fn view_collateral_value(&self, user: AccountId, id: u128) -> Result<u128, MarketError> {
+            let position = self.get_position(user, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            let price = self.get_price(position.collateral_asset)?;
+            self.calculate_usd_from_asset_amount(
+                position.collateral_amount,
+                self.get_decimals(position.collateral_asset),
+                price,
+            )
         }
 
         #[ink(message)]
         pub fn view_all(
             &self,
             user: AccountId,
-        ) -> Result<Vec<(Position, i128, u128)>, MarketError> {
+        ) -> Result<Vec<PositionView>, MarketError> {
             let positions = self.view_positions(user);
             let new_price = self.view_market_price()?;
 
             let mut data = Vec::new();
             for position in positions {
-                let pnl = self.calculate_pnl_percent(position.entry_price, new_price, position.leverage, position.is_long)?;
-                data.push((position, pnl, new_price));
+                let max_profit_usd = self.calculate_max_profit_usd(position.collateral_usd)?;
+                let pnl_usd = self
+                    .calculate_net_pnl_usd(&position, new_price)?
+                    .min(max_profit_usd as i128);
+                let roe = self.calculate_roe(pnl_usd, position.collateral_usd)?;
+                data.push((position, pnl_usd, roe, new_price, max_profit_usd));
             }
 
             Ok(data)
         }
 
-        fn get_symbol_and_decimals(&self, token: AccountId) -> Result<(String, u8), MarketError> {
-            let metadata: contract_ref!(PSP22Metadata) = token.into();
-
-            let symbol = metadata.token_symbol().ok_or(MarketError::OracleFailed)?;
+        /// Positions of `users` whose net profit has reached the max profit multiple and
+        /// can be closed by a keeper through `execute_trigger`.
+        #[ink(message)]
+        pub fn view_capped_positions(&self, users: Vec<AccountId>) -> Result<Vec<Position>, MarketError> {
+            let current_price = self.get_price(self.underlying_asset)?;
+
+            let mut capped = Vec::new();
+            for user in users {
+                for position in self.view_positions(user) {
+                    if self.is_profit_capped(&position, current_price)? {
+                        capped.push(position);
+                    }
+                }
+            }
 
-            let decimals = metadata.token_decimals();
+            Ok(capped)
+        }
 
-            Ok((symbol, decimals))
+        /// (open fee, close fee, hourly borrow fee, owner's share of fees), all in basis points.
+        #[ink(message)]
+        pub fn view_fees(&self) -> (u16, u16, u16, u16) {
+            (self.open_fee, self.close_fee, self.borrow_fee, self.protocol_fee_share)
         }
 
-        fn calculate_usd_from_asset_amount(
+        /// Borrow fee accrued by a position in USD.
+        #[ink(message)]
+        pub fn view_position_borrow_fee(
             &self,
-            asset_amount: u128,
-            asset_decimals: u8,
-            price: u128,
+            user: AccountId,
+            id: u128,
         ) -> Result<u128, MarketError> {
-            asset_amount
-                .checked_mul(price)
-                .ok_or(MarketError::Overflow(String::from("calculate_usd_from_asset_amount_1")))?
-                .checked_div(
-                    10u128.checked_pow(asset_decimals as u32)
-                        .ok_or(MarketError::Overflow(String::from("calculate_usd_from_asset_amount_2")))?
-                )
-                .ok_or(MarketError::Overflow(String::from("calculate_usd_from_asset_amount_3")))
+            let position = self.get_position(user, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            self.calculate_borrow_fee_usd(&position)
         }
 
-        fn calculate_pnl_percent(
-            &self,
-            old_price: u128,
-            new_price: u128,
-            leverage: u8,
-            is_long: bool,
-        ) -> Result<i128, MarketError> {
-            let sign = if is_long { 1 } else { -1 };
-            (new_price as i128)
-                .checked_sub(old_price as i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_pnl_percent_1")))?
-                .checked_mul(sign)
-                .ok_or(MarketError::Overflow(String::from("calculate_pnl_percent_2")))?
-                .checked_mul(leverage as i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_pnl_percent_3")))?
-                .checked_mul(100)
-                .ok_or(MarketError::Overflow(String::from("calculate_pnl_percent_4")))?
-                .checked_div(old_price as i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_pnl_percent_5")))
+        #[ink(message)]
+        pub fn view_protocol_fees(&self, asset: AccountId) -> u128 {
+            self.protocol_fees.get(asset).unwrap_or_default()
         }
 
-        fn calculate_asset_amount_from_usd(
-            &self,
-            usd_amount: u128,
-            price: u128,
-            asset_decimals: u8,
-        ) -> Result<u128, MarketError> {
-            usd_amount
-                .checked_mul(
-                    10u128.checked_pow(asset_decimals as u32)
-                        .ok_or(MarketError::Overflow(String::from("calculate_asset_amount_from_usd_1")))?
-                )
-                .ok_or(MarketError::Overflow(String::from("calculate_asset_amount_from_usd_2")))?
-                .checked_div(price)
-                .ok_or(MarketError::Overflow(String::from("calculate_asset_amount_from_usd_3")))
+        #[ink(message)]
+        pub fn claim_protocol_fees(&mut self, asset: AccountId, to: AccountId) -> Result<u128, MarketError> {
+            self.ensure_role(Role::FeeCollector)?;
+
+            let amount = self.protocol_fees.get(asset).unwrap_or_default();
+            if amount == 0 {
+                return Ok(0);
+            }
+
+            self.protocol_fees.remove(asset);
+
+            let mut token: contract_ref!(PSP22) = asset.into();
+            token
+                .transfer(to, amount, Vec::new())
+                .map_err(|_| MarketError::TransferFailed)?;
+
+            self.emit_event(ProtocolFeesClaimed {
+                asset,
+                to,
+                amount,
+            });
+
+            Ok(amount)
         }
 
-        fn wrap_native(&self, transferred_amount: u128) -> Result<(), MarketError> {
-            let call_result: Result<(), PSP22Error> =
-                build_call::<DefaultEnvironment>()
-                    .call(self.wazero)
-                    .exec_input(ExecutionInput::new(Selector::new(WAZERO_DEPOSIT_SELECTOR)))
-                    .transferred_value(transferred_amount)
-                    .returns::<Result<(), PSP22Error>>()
-                    .invoke();
+        #[ink(message)]
+        pub fn view_market_config(&self) -> MarketConfig {
+            MarketConfig {
+                min_leverage: self.min_leverage,
+                max_leverage: self.max_leverage,
+                min_collateral_usd: self.min_collateral_usd,
+                max_position_notional: self.max_position_notional,
+                liquidation_threshold: self.liquidation_threshold,
+                liquidation_penalty: self.liquidation_penalty,
+                protocol_fee: self.protocol_fee,
+            }
+        }
 
-            call_result
-                .map_err(|_| MarketError::LangError)
+        #[ink(message)]
+        pub fn view_paused_scopes(&self) -> Vec<PauseScope> {
+            self.paused_scopes.clone()
         }
 
-        fn calculate_amount_and_mint(
-            &mut self,
-            caller: AccountId,
-            amount: u128,
-        ) -> Result<(), MarketError> {
-            let contract = self.env().account_id();
-            let underlying_asset: contract_ref!(PSP22) = self.underlying_asset.into();
+        #[ink(message)]
+        pub fn is_paused(&self, scope: PauseScope) -> bool {
+            self.paused_scopes.contains(&scope)
+        }
 
-            let deposit_token_amount: u128;
-            if self.total_supply() == 0 {
-                deposit_token_amount = amount;
-            } else {
-                deposit_token_amount = amount
-                    .checked_mul(self.total_supply())
-                    .ok_or(MarketError::Overflow(String::from("calculate_amount_and_mint_1")))?
-                    .checked_div(underlying_asset.balance_of(contract))
-                    .ok_or(MarketError::Overflow(String::from("calculate_amount_and_mint_2")))?;
+        /// Pauses or resumes a single group of entry points, callable by guardians and admins.
+        #[ink(message)]
+        pub fn set_paused(&mut self, scope: PauseScope, paused: bool) -> Result<(), MarketError> {
+            self.ensure_role(Role::Guardian)?;
+
+            let index = self.paused_scopes.iter().position(|&x| x == scope);
+            match (index, paused) {
+                (None, true) => self.paused_scopes.push(scope),
+                (Some(index), false) => {
+                    self.paused_scopes.swap_remove(index);
+                }
+                _ => return Ok(()),
             }
 
-            self.data
-                .mint(caller, deposit_token_amount)
-                .map_err(|_| MarketError::MintFailed)?;
+            self.emit_event(PauseUpdated { scope, paused });
 
             Ok(())
         }
 
-        fn burn_and_calculate_amount(
-            &mut self,
-            caller: AccountId,
-            deposit_token_amount: u128,
-        ) -> Result<u128, MarketError> {
-            let contract = self.env().account_id();
-            let underlying_asset: contract_ref!(PSP22) = self.underlying_asset.into();
+        #[ink(message)]
+        pub fn is_deprecated(&self) -> bool {
+            self.deprecated.get().unwrap_or_default()
+        }
 
-            let token_amount = deposit_token_amount
-                .checked_mul(underlying_asset.balance_of(contract))
-                .ok_or(MarketError::Overflow(String::from("burn_and_calculate_amount_1")))?
-                .checked_div(self.total_supply())
-                .ok_or(MarketError::Overflow(String::from("burn_and_calculate_amount_2")))?;
+        /// Winds the market down for good, callable by admins. Unlike pausing this cannot be
+        /// undone, new positions, orders and deposits are rejected while closes, liquidations
+        /// and LP withdrawals keep working.
+        #[ink(message)]
+        pub fn deprecate(&mut self) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
 
-            self.data
-                .burn(caller, deposit_token_amount)
-                .map_err(|_| MarketError::BurnFailed)?;
+            if self.is_deprecated() {
+                return Ok(());
+            }
 
-            Ok(token_amount)
+            self.deprecated.set(&true);
+            self.emit_event(MarketDeprecated {});
+
+            Ok(())
         }
 
-        fn open_position(
-            &mut self,
-            collateral_asset: AccountId,
-            collateral_amount: Balance,
-            is_long: bool,
-            leverage: u8,
-            caller: AccountId,
-        ) -> Result<(), MarketError> {
-            let mut collateral: contract_ref!(PSP22) = collateral_asset.into();
+        #[ink(message)]
+        pub fn view_config_timelock(&self) -> u32 {
+            self.config_timelock
+        }
 
-            if self.total_supply() <= 0 {
-                return Err(MarketError::MissingDeposits);
+        #[ink(message)]
+        pub fn view_config_changes(&self) -> Vec<PendingConfigChange> {
+            let mut changes = Vec::new();
+
+            for id in self.config_change_ids.iter() {
+                if let Some(change) = self.config_changes.get(id) {
+                    changes.push(change);
+                }
             }
 
-            let (collateral_symbol, collateral_decimals) =
-                self.get_symbol_and_decimals(collateral_asset)?;
-            let collateral_price = self.get_price(collateral_symbol)?;
+            changes
+        }
 
-            let collateral_usd = self.calculate_usd_from_asset_amount(
-                collateral_amount,
-                collateral_decimals,
-                collateral_price,
-            )?;
+        /// Queues a change to a risk parameter or dependency, applicable with
+        /// `execute_config_change` once `config_timelock` blocks have passed.
+        #[ink(message)]
+        pub fn queue_config_change(&mut self, change: ConfigChange) -> Result<u128, MarketError> {
+            self.ensure_role(Role::Admin)?;
 
-            let (symbol, _decimals) = self.get_symbol_and_decimals(self.underlying_asset)?;
-            let entry_price = self.get_price(symbol)?;
+            let is_valid = match change {
+                ConfigChange::LiquidationThreshold(threshold) => {
+                    threshold < 0 && threshold > -(BASIS_POINTS as i32)
+                }
+                ConfigChange::LiquidationPenalty(fee)
+                | ConfigChange::ProtocolFee(fee)
+                | ConfigChange::MaxUtilisation(fee) => fee as u128 <= BASIS_POINTS,
+                ConfigChange::Fees(open_fee, close_fee, borrow_fee, protocol_fee_share) => {
+                    [open_fee, close_fee, borrow_fee, protocol_fee_share]
+                        .iter()
+                        .all(|&fee| fee as u128 <= BASIS_POINTS)
+                }
+                ConfigChange::PositionLimits(min_leverage, max_leverage, _, _) => {
+                    if min_leverage == 0 || min_leverage > max_leverage {
+                        return Err(MarketError::InvalidLeverage);
+                    }
+                    true
+                }
+                ConfigChange::MaxProfitMultiple(max_profit_multiple) => max_profit_multiple > 0,
+                _ => true,
+            };
 
-            let liquidation_price = self.calculate_liquidation_price(entry_price, leverage, is_long)?;
+            if !is_valid {
+                return Err(MarketError::InvalidAmount);
+            }
 
-            let id = self.new_id.get(caller).unwrap_or_default();
-            self.positions.insert(
-                (caller, id),
-                &Position::new(
-                    caller,
-                    id,
-                    collateral_amount,
-                    collateral_asset,
-                    collateral_usd,
-                    entry_price,
-                    leverage,
-                    is_long,
-                    self.env().block_number(),
-                    liquidation_price,
-                ),
-            );
+            let eta = self.env()
+                .block_number()
+                .checked_add(self.config_timelock)
+                .ok_or(MarketError::Overflow(String::from("queue_config_change_1")))?;
+
+            let id = self.next_config_change_id;
+            self.next_config_change_id = id
+                .checked_add(1)
+                .ok_or(MarketError::Overflow(String::from("queue_config_change_2")))?;
+
+            self.config_changes.insert(id, &PendingConfigChange {
+                id,
+                change: change.clone(),
+                eta,
+            });
+            self.config_change_ids.push(id);
+
+            self.emit_event(ConfigChangeQueued {
+                id,
+                change,
+                eta,
+            });
+
+            Ok(id)
+        }
 
-            let mut ids_for_user = self.ids_per_user.get(caller).unwrap_or_default();
-            ids_for_user.push(id);
-            self.ids_per_user.insert(caller, &ids_for_user);
+        #[ink(message)]
+        pub fn execute_config_change(&mut self, id: u128) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
 
-            collateral
-                .approve(self.vault, collateral_amount)
-                .map_err(|_| MarketError::ApproveFailed)?;
+            let pending = self.config_changes
+                .get(id)
+                .ok_or(MarketError::ConfigChangeNotFound)?;
 
-            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
-            vault
-                .deposit(caller, id, collateral_asset, collateral_amount)
-                .map_err(|err| MarketError::VaultError(err))?;
+            if self.env().block_number() < pending.eta {
+                return Err(MarketError::ConfigChangeNotReady);
+            }
 
-            self.new_id.insert(caller, &id.saturating_add(1));
+            match pending.change {
+                ConfigChange::LiquidationThreshold(threshold) => self.liquidation_threshold = threshold,
+                ConfigChange::LiquidationPenalty(penalty) => self.liquidation_penalty = penalty,
+                ConfigChange::ProtocolFee(protocol_fee) => self.protocol_fee = protocol_fee,
+                ConfigChange::Oracle(oracle) => self.oracle = oracle,
+                ConfigChange::Vault(vault) => {
+                    // collateral of open positions and order escrow is held by the current vault
+                    if self.long_open_interest > 0 || self.short_open_interest > 0 {
+                        return Err(MarketError::PositionsOpen);
+                    }
+                    if self.open_orders.get().unwrap_or_default() > 0 {
+                        return Err(MarketError::OrdersOpen);
+                    }
+                    self.vault = vault;
+                }
+                ConfigChange::Timelock(timelock) => self.config_timelock = timelock,
+                ConfigChange::OracleGuards(max_price_age, max_price_deviation) => {
+                    self.max_price_age = max_price_age;
+                    self.max_price_deviation = max_price_deviation;
+                }
+                ConfigChange::Fees(open_fee, close_fee, borrow_fee, protocol_fee_share) => {
+                    // borrow fees up to now accrue at the old rate
+                    self.accrue_funding()?;
+                    self.open_fee = open_fee;
+                    self.close_fee = close_fee;
+                    self.borrow_fee = borrow_fee;
+                    self.protocol_fee_share = protocol_fee_share;
+                }
+                ConfigChange::PositionLimits(
+                    min_leverage,
+                    max_leverage,
+                    min_collateral_usd,
+                    max_position_notional,
+                ) => {
+                    self.min_leverage = min_leverage;
+                    self.max_leverage = max_leverage;
+                    self.min_collateral_usd = min_collateral_usd;
+                    self.max_position_notional = max_position_notional;
+                }
+                ConfigChange::MaxProfitMultiple(max_profit_multiple) => {
+                    self.max_profit_multiple = max_profit_multiple;
+                }
+                ConfigChange::MaxUtilisation(max_utilisation) => self.max_utilisation = max_utilisation,
+                ConfigChange::FundingRateFactor(funding_rate_factor) => {
+                    // funding up to now accrues at the old rate
+                    self.accrue_funding()?;
+                    self.funding_rate_factor = funding_rate_factor;
+                }
+            }
 
-            Ok(())
-        }
+            self.remove_config_change(id);
 
-        fn calculate_liquidation_price(
-            &self,
-            entry_price: u128,
-            leverage: u8,
-            is_long: bool, 
-        ) -> Result<u128, MarketError> {
-            let sign: i128 = if is_long { 1 } else { -1 };
-            let result: i128 = (entry_price as i128)
-                .checked_mul(self.liquidation_threshold as i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_1")))?
-                .checked_div(leverage as i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_2")))?
-                .checked_div(100i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_3")))?
-                .checked_mul(sign)
-                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_4")))?
-                .checked_add(entry_price as i128)
-                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_5")))?;
+            self.emit_event(ConfigChangeExecuted {
+                id,
+                change: pending.change,
+            });
 
-            Ok(result as u128)
+            Ok(())
         }
 
-        #[ink(message, payable)]
-        pub fn deposit_native(&mut self) -> Result<(), MarketError> {
-            let caller = self.env().caller();
+        #[ink(message)]
+        pub fn cancel_config_change(&mut self, id: u128) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
 
-            let transferred_amount = self.env().transferred_value();
-            self.wrap_native(transferred_amount)?;
+            if !self.config_changes.contains(id) {
+                return Err(MarketError::ConfigChangeNotFound);
+            }
 
-            self.calculate_amount_and_mint(caller, transferred_amount)?;
+            self.remove_config_change(id);
+
+            self.emit_event(ConfigChangeCancelled { id });
 
             Ok(())
         }
 
         #[ink(message)]
-        pub fn deposit(&mut self, amount: u128) -> Result<(), MarketError> {
+        pub fn view_max_profit_multiple(&self) -> u8 {
+            self.max_profit_multiple
+        }
+
+        /// Funding rate per block scaled by 1e12, positive when longs pay shorts.
+        #[ink(message)]
+        pub fn view_funding_rate(&self) -> Result<i128, MarketError> {
+            self.calculate_funding_rate()
+        }
+
+        /// Funding accrued by a position in USD, positive when the position owes funding.
+        #[ink(message)]
+        pub fn view_position_funding(
+            &self,
+            user: AccountId,
+            id: u128,
+        ) -> Result<i128, MarketError> {
+            let position = self.get_position(user, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            self.calculate_funding_usd(&position)
+        }
+
+        /// Aggregate (long, short) open interest in USD with 6 decimals.
+        #[ink(message)]
+        pub fn view_open_interest(&self) -> (u128, u128) {
+            (self.long_open_interest, self.short_open_interest)
+        }
+
+        /// Underlying asset backing the LP token, net of traders' unrealised PnL.
+        #[ink(message)]
+        pub fn view_pool_value(&self) -> Result<u128, MarketError> {
+            self.calculate_pool_value()
+        }
+
+        /// Underlying asset per whole LP token, scaled by the LP token decimals.
+        #[ink(message)]
+        pub fn view_share_price(&self) -> Result<u128, MarketError> {
+            let one_share = 10u128
+                .checked_pow(self.decimals as u32)
+                .ok_or(MarketError::Overflow(String::from("view_share_price_1")))?;
+
+            if self.total_supply() == 0 {
+                return Ok(one_share);
+            }
+
+            self.calculate_pool_value()?
+                .checked_mul(one_share)
+                .ok_or(MarketError::Overflow(String::from("view_share_price_2")))?
+                .checked_div(self.total_supply())
+                .ok_or(MarketError::Overflow(String::from("view_share_price_3")))
+        }
+
+        /// Reserved underlying, underlying still withdrawable by LPs, and the utilisation cap in basis points.
+        #[ink(message)]
+        pub fn view_reserved_liquidity(&self) -> (u128, u128, u16) {
+            (self.reserved_amount, self.calculate_available_liquidity(), self.max_utilisation)
+        }
+
+        #[ink(message)]
+        pub fn view_open_interest_caps(&self) -> (u128, u128) {
+            (self.max_long_open_interest, self.max_short_open_interest)
+        }
+
+        #[ink(message)]
+        pub fn set_open_interest_caps(
+            &mut self,
+            max_long_open_interest: u128,
+            max_short_open_interest: u128,
+        ) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.max_long_open_interest = max_long_open_interest;
+            self.max_short_open_interest = max_short_open_interest;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_trigger_fee(&mut self, trigger_fee: u16) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            if trigger_fee as u128 > BASIS_POINTS {
+                return Err(MarketError::InvalidAmount);
+            }
+
+            self.trigger_fee = trigger_fee;
+
+            Ok(())
+        }
+
+        /// Replaces the contract code, storage is kept as is. `migrate` has to be called
+        /// afterwards if the new code changes the storage layout.
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: Hash) -> Result<(), MarketError> {
+            self.ensure_owner()?;
+
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| MarketError::UpgradeFailed)?;
+
+            self.emit_event(Upgraded { code_hash });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn view_storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        /// Brings the storage written by older code up to `STORAGE_VERSION`.
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<(), MarketError> {
+            self.ensure_owner()?;
+
+            let from_version = self.storage_version;
+            if from_version >= STORAGE_VERSION {
+                return Ok(());
+            }
+
+            // no layout has changed since version 1 yet, transformations of data written
+            // by older code are applied here based on `from_version`
+            self.storage_version = STORAGE_VERSION;
+
+            self.emit_event(Migrated {
+                from_version,
+                to_version: STORAGE_VERSION,
+            });
+
+            Ok(())
+        }
+
+        // Other contracts in the dependency tree also implement `EmitEvent` for the
+        // environment, so the emitting contract has to be named explicitly.
+        fn emit_event<E>(&self, event: E)
+        where
+            E: Into<<Market as ContractEventBase>::Type>,
+        {
+            EmitEvent::<Market>::emit_event(self.env(), event);
+        }
+
+        fn emit_events(&self, events: Vec<PSP22Event>) {
+            for event in events {
+                match event {
+                    PSP22Event::Transfer { from, to, value } => {
+                        self.emit_event(Transfer { from, to, value })
+                    }
+                    PSP22Event::Approval {
+                        owner,
+                        spender,
+                        amount,
+                    } => self.emit_event(Approval {
+                        owner,
+                        spender,
+                        amount,
+                    }),
+                }
+            }
+        }
+
+        fn emit_access_control_events(&self, events: Vec<AccessControlEvent>) {
+            for event in events {
+                match event {
+                    AccessControlEvent::OwnershipTransferStarted {
+                        previous_owner,
+                        new_owner,
+                    } => self.emit_event(OwnershipTransferStarted {
+                        previous_owner,
+                        new_owner,
+                    }),
+                    AccessControlEvent::OwnershipTransferred {
+                        previous_owner,
+                        new_owner,
+                    } => self.emit_event(OwnershipTransferred {
+                        previous_owner,
+                        new_owner,
+                    }),
+                    AccessControlEvent::RoleGranted {
+                        role,
+                        account,
+                        sender,
+                    } => self.emit_event(RoleGranted {
+                        role,
+                        account,
+                        sender,
+                    }),
+                    AccessControlEvent::RoleRevoked {
+                        role,
+                        account,
+                        sender,
+                    } => self.emit_event(RoleRevoked {
+                        role,
+                        account,
+                        sender,
+                    }),
+                }
+            }
+        }
+
+        // The stored liquidation price is refreshed on read, so it follows changes to the
+        // liquidation threshold. Liquidations themselves check the threshold against the PnL.
+        fn get_position(&self, user: AccountId, id: u128) -> Option<Position> {
+            let mut position = self.positions
+                .get((user, id))
+                .map(|stored| stored.into_latest())?;
+
+            if let Ok(liquidation_price) =
+                self.calculate_liquidation_price(position.entry_price, position.leverage, position.is_long)
+            {
+                position.liquidation_price = liquidation_price;
+            }
+
+            Some(position)
+        }
+
+        fn insert_position(&mut self, position: &Position) {
+            self.positions.insert(
+                (position.user, position.id),
+                &VersionedPosition::from(position.clone()),
+            );
+        }
+
+        fn ensure_owner(&self) -> Result<(), MarketError> {
+            self.access_control
+                .ensure_owner(self.env().caller())
+                .map_err(MarketError::AccessControlError)
+        }
+
+        fn ensure_role(&self, role: Role) -> Result<(), MarketError> {
+            self.access_control
+                .ensure_role(self.env().caller(), role)
+                .map_err(MarketError::AccessControlError)
+        }
+
+        fn emit_position_updated(&self, position: &Position) {
+            self.emit_event(PositionUpdated {
+                user: position.user,
+                id: position.id,
+                collateral_amount: position.collateral_amount,
+                collateral_usd: position.collateral_usd,
+                entry_price: position.entry_price,
+                leverage: position.leverage,
+                liquidation_price: position.liquidation_price,
+                take_profit: position.take_profit,
+                stop_loss: position.stop_loss,
+            });
+        }
+
+        fn get_decimals(&self, token: AccountId) -> u8 {
+            let metadata: contract_ref!(PSP22Metadata) = token.into();
+            metadata.token_decimals()
+        }
+
+        // Registered pair and oracle decimals for `asset`, or the legacy pair derived from
+        // the token symbol ("wAZERO" => "AZERO/USD") when the fallback is enabled.
+        fn get_oracle_pair(&self, asset: AccountId) -> Result<(String, u8), MarketError> {
+            if let Some(oracle_pair) = self.oracle_pairs.get(asset) {
+                return Ok(oracle_pair);
+            }
+
+            if !self.symbol_fallback {
+                return Err(MarketError::OracleNotConfigured);
+            }
+
+            let metadata: contract_ref!(PSP22Metadata) = asset.into();
+            let symbol = metadata.token_symbol().ok_or(MarketError::OracleFailed)?;
+            let unwrapped_symbol = symbol.get(1..).filter(|s| !s.is_empty())
+                .ok_or(MarketError::OracleNotConfigured)?;
+
+            // DIA price oracle returns USD price with 18 decimals by default
+            Ok((format!("{unwrapped_symbol}/USD"), 18))
+        }
+
+        fn calculate_usd_from_asset_amount(
+            &self,
+            asset_amount: u128,
+            asset_decimals: u8,
+            price: u128,
+        ) -> Result<u128, MarketError> {
+            asset_amount
+                .checked_mul(price)
+                .ok_or(MarketError::Overflow(String::from("calculate_usd_from_asset_amount_1")))?
+                .checked_div(
+                    10u128.checked_pow(asset_decimals as u32)
+                        .ok_or(MarketError::Overflow(String::from("calculate_usd_from_asset_amount_2")))?
+                )
+                .ok_or(MarketError::Overflow(String::from("calculate_usd_from_asset_amount_3")))
+        }
+
+        // Price PnL in USD of the whole position: size in underlying units times the price move.
+        fn calculate_pnl_usd(
+            &self,
+            position: &Position,
+            current_price: u128,
+        ) -> Result<i128, MarketError> {
+            let sign: i128 = if position.is_long { 1 } else { -1 };
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+            let size = self.calculate_size(notional_usd, position.entry_price)?;
+
+            (current_price as i128)
+                .checked_sub(position.entry_price as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_pnl_usd_1")))?
+                .checked_mul(sign)
+                .ok_or(MarketError::Overflow(String::from("calculate_pnl_usd_2")))?
+                .checked_mul(size as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_pnl_usd_3")))?
+                .checked_div(SIZE_PRECISION as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_pnl_usd_4")))
+        }
+
+        // Price PnL net of accrued funding and borrow fees.
+        fn calculate_net_pnl_usd(
+            &self,
+            position: &Position,
+            current_price: u128,
+        ) -> Result<i128, MarketError> {
+            self.calculate_pnl_usd(position, current_price)?
+                .checked_sub(self.calculate_funding_usd(position)?)
+                .ok_or(MarketError::Overflow(String::from("calculate_net_pnl_usd_1")))?
+                .checked_sub(self.calculate_borrow_fee_usd(position)? as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_net_pnl_usd_2")))
+        }
+
+        /// Return on collateral in basis points.
+        fn calculate_roe(&self, pnl_usd: i128, collateral_usd: u128) -> Result<i128, MarketError> {
+            pnl_usd
+                .checked_mul(BASIS_POINTS as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_roe_1")))?
+                .checked_div(collateral_usd as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_roe_2")))
+        }
+
+        fn is_below_liquidation_threshold(
+            &self,
+            position: &Position,
+            current_price: u128,
+        ) -> Result<bool, MarketError> {
+            let net_pnl_usd = self
+                .calculate_net_pnl_usd(position, current_price)?
+                .checked_mul(BASIS_POINTS as i128)
+                .ok_or(MarketError::Overflow(String::from("is_below_liquidation_threshold_1")))?;
+
+            let threshold_usd = (position.collateral_usd as i128)
+                .checked_mul(self.liquidation_threshold as i128)
+                .ok_or(MarketError::Overflow(String::from("is_below_liquidation_threshold_2")))?;
+
+            Ok(net_pnl_usd <= threshold_usd)
+        }
+
+        fn calculate_max_profit_usd(&self, collateral_usd: u128) -> Result<u128, MarketError> {
+            collateral_usd
+                .checked_mul(self.max_profit_multiple as u128)
+                .ok_or(MarketError::Overflow(String::from("calculate_max_profit_usd")))
+        }
+
+        fn is_profit_capped(&self, position: &Position, current_price: u128) -> Result<bool, MarketError> {
+            let net_pnl_usd = self.calculate_net_pnl_usd(position, current_price)?;
+
+            Ok(net_pnl_usd >= self.calculate_max_profit_usd(position.collateral_usd)? as i128)
+        }
+
+        fn calculate_leverage(
+            &self,
+            notional_usd: u128,
+            collateral_usd: u128,
+        ) -> Result<u32, MarketError> {
+            let leverage = notional_usd
+                .checked_mul(BASIS_POINTS)
+                .ok_or(MarketError::Overflow(String::from("calculate_leverage_1")))?
+                .checked_div(collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("calculate_leverage_2")))?;
+
+            if leverage == 0 {
+                return Err(MarketError::InvalidLeverage);
+            }
+
+            u32::try_from(leverage).map_err(|_| MarketError::InvalidLeverage)
+        }
+
+        fn calculate_asset_amount_from_usd(
+            &self,
+            usd_amount: u128,
+            price: u128,
+            asset_decimals: u8,
+        ) -> Result<u128, MarketError> {
+            usd_amount
+                .checked_mul(
+                    10u128.checked_pow(asset_decimals as u32)
+                        .ok_or(MarketError::Overflow(String::from("calculate_asset_amount_from_usd_1")))?
+                )
+                .ok_or(MarketError::Overflow(String::from("calculate_asset_amount_from_usd_2")))?
+                .checked_div(price)
+                .ok_or(MarketError::Overflow(String::from("calculate_asset_amount_from_usd_3")))
+        }
+
+        fn wrap_native(&self, transferred_amount: u128) -> Result<(), MarketError> {
+            let call_result: Result<(), PSP22Error> =
+                build_call::<DefaultEnvironment>()
+                    .call(self.wazero)
+                    .exec_input(ExecutionInput::new(Selector::new(WAZERO_DEPOSIT_SELECTOR)))
+                    .transferred_value(transferred_amount)
+                    .returns::<Result<(), PSP22Error>>()
+                    .invoke();
+
+            call_result
+                .map_err(|_| MarketError::LangError)
+        }
+
+        fn calculate_amount_and_mint(
+            &mut self,
+            caller: AccountId,
+            amount: u128,
+        ) -> Result<(), MarketError> {
+            let deposit_token_amount: u128;
+            if self.total_supply() == 0 {
+                deposit_token_amount = amount;
+            } else {
+                // the deposit has already been received, price shares on the value before it
+                let pool_value = self.calculate_pool_value()?
+                    .checked_sub(amount)
+                    .ok_or(MarketError::Overflow(String::from("calculate_amount_and_mint_1")))?;
+
+                deposit_token_amount = amount
+                    .checked_mul(self.total_supply())
+                    .ok_or(MarketError::Overflow(String::from("calculate_amount_and_mint_2")))?
+                    .checked_div(pool_value)
+                    .ok_or(MarketError::Overflow(String::from("calculate_amount_and_mint_3")))?;
+            }
+
+            let events = self.data
+                .mint(caller, deposit_token_amount)
+                .map_err(|_| MarketError::MintFailed)?;
+            self.emit_events(events);
+
+            self.emit_event(LiquidityAdded {
+                provider: caller,
+                amount,
+                shares: deposit_token_amount,
+            });
+
+            Ok(())
+        }
+
+        // Underlying held by the market that belongs to LPs.
+        fn calculate_liquidity(&self) -> u128 {
+            let contract = self.env().account_id();
+            let underlying_asset: contract_ref!(PSP22) = self.underlying_asset.into();
+
+            underlying_asset
+                .balance_of(contract)
+                .saturating_sub(self.protocol_fees.get(self.underlying_asset).unwrap_or_default())
+        }
+
+        fn calculate_available_liquidity(&self) -> u128 {
+            self.calculate_liquidity().saturating_sub(self.reserved_amount)
+        }
+
+        fn burn_and_calculate_amount(
+            &mut self,
+            caller: AccountId,
+            deposit_token_amount: u128,
+        ) -> Result<u128, MarketError> {
+            let token_amount = deposit_token_amount
+                .checked_mul(self.calculate_pool_value()?)
+                .ok_or(MarketError::Overflow(String::from("burn_and_calculate_amount_1")))?
+                .checked_div(self.total_supply())
+                .ok_or(MarketError::Overflow(String::from("burn_and_calculate_amount_2")))?;
+
+            if token_amount > self.calculate_available_liquidity() {
+                return Err(MarketError::InsufficientLiquidity);
+            }
+
+            let events = self.data
+                .burn(self.env().account_id(), deposit_token_amount)
+                .map_err(|_| MarketError::BurnFailed)?;
+            self.emit_events(events);
+
+            self.emit_event(LiquidityRemoved {
+                provider: caller,
+                amount: token_amount,
+                shares: deposit_token_amount,
+            });
+
+            Ok(token_amount)
+        }
+
+        fn open_position(
+            &mut self,
+            collateral_asset: AccountId,
+            collateral_amount: Balance,
+            is_long: bool,
+            leverage: u32,
+            take_profit: Option<u128>,
+            stop_loss: Option<u128>,
+            caller: AccountId,
+        ) -> Result<(), MarketError> {
+            let mut collateral: contract_ref!(PSP22) = collateral_asset.into();
+
+            let id = self.new_id.get(caller).unwrap_or_default();
+
+            collateral
+                .approve(self.vault, collateral_amount)
+                .map_err(|_| MarketError::ApproveFailed)?;
+
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .deposit(caller, id, collateral_asset, collateral_amount)
+                .map_err(|err| MarketError::VaultError(err))?;
+
+            self.create_position(
+                caller,
+                id,
+                collateral_asset,
+                collateral_amount,
+                is_long,
+                leverage,
+                take_profit,
+                stop_loss,
+            )?;
+
+            self.new_id.insert(caller, &id.saturating_add(1));
+
+            Ok(())
+        }
+
+        fn create_position(
+            &mut self,
+            caller: AccountId,
+            id: u128,
+            collateral_asset: AccountId,
+            collateral_amount: Balance,
+            is_long: bool,
+            leverage: u32,
+            take_profit: Option<u128>,
+            stop_loss: Option<u128>,
+        ) -> Result<(), MarketError> {
+            if self.total_supply() <= 0 {
+                return Err(MarketError::MissingDeposits);
+            }
+
+            self.accrue_funding()?;
+
+            let collateral_decimals = self.get_decimals(collateral_asset);
+            let collateral_price = self.fetch_price(collateral_asset)?;
+
+            let collateral_usd = self.calculate_usd_from_asset_amount(
+                collateral_amount,
+                collateral_decimals,
+                collateral_price,
+            )?;
+
+            self.validate_leverage(leverage)?;
+
+            let (collateral_amount, collateral_usd) = self.charge_open_fee(
+                caller,
+                id,
+                collateral_asset,
+                collateral_amount,
+                collateral_usd,
+                leverage,
+            )?;
+
+            self.validate_position_size(collateral_usd, leverage)?;
+
+            let entry_price = self.fetch_price(self.underlying_asset)?;
+
+            let liquidation_price = self.calculate_liquidation_price(entry_price, leverage, is_long)?;
+
+            let notional_usd = self.calculate_notional_usd(collateral_usd, leverage)?;
+            let reserved_amount = self.increase_open_interest(notional_usd, collateral_usd, entry_price, is_long)?;
+
+            self.insert_position(
+                &Position::new(
+                    caller,
+                    id,
+                    collateral_amount,
+                    collateral_asset,
+                    collateral_usd,
+                    entry_price,
+                    leverage,
+                    is_long,
+                    self.env().block_number(),
+                    liquidation_price,
+                    self.funding_index,
+                    self.borrow_index,
+                    reserved_amount,
+                    take_profit,
+                    stop_loss,
+                ),
+            );
+
+            let mut ids_for_user = self.ids_per_user.get(caller).unwrap_or_default();
+            ids_for_user.push(id);
+            self.ids_per_user.insert(caller, &ids_for_user);
+
+            self.emit_event(PositionOpened {
+                user: caller,
+                id,
+                collateral_asset,
+                collateral_amount,
+                collateral_usd,
+                entry_price,
+                leverage,
+                is_long,
+            });
+
+            Ok(())
+        }
+
+        // Escrows collateral plus keeper fee in the vault under the position id the
+        // order will take once executed.
+        fn place_order(
+            &mut self,
+            caller: AccountId,
+            collateral_asset: AccountId,
+            collateral_amount: Balance,
+            execution_fee: Balance,
+            trigger_price: u128,
+            is_long: bool,
+            leverage: u32,
+            block_expiry: u32,
+        ) -> Result<u128, MarketError> {
+            if collateral_amount == 0 {
+                return Err(MarketError::InvalidAmount);
+            }
+
+            self.validate_leverage(leverage)?;
+
+            if block_expiry <= self.env().block_number() {
+                return Err(MarketError::OrderExpired);
+            }
+
+            let escrow_amount = collateral_amount
+                .checked_add(execution_fee)
+                .ok_or(MarketError::Overflow(String::from("place_order")))?;
+
+            let id = self.new_id.get(caller).unwrap_or_default();
+            self.orders.insert(
+                (caller, id),
+                &Order::new(
+                    caller,
+                    id,
+                    collateral_amount,
+                    collateral_asset,
+                    execution_fee,
+                    trigger_price,
+                    leverage,
+                    is_long,
+                    self.env().block_number(),
+                    block_expiry,
+                ),
+            );
+
+            let mut order_ids_for_user = self.order_ids_per_user.get(caller).unwrap_or_default();
+            order_ids_for_user.push(id);
+            self.order_ids_per_user.insert(caller, &order_ids_for_user);
+
+            let open_orders = self.open_orders
+                .get()
+                .unwrap_or_default()
+                .checked_add(1)
+                .ok_or(MarketError::Overflow(String::from("place_order")))?;
+            self.open_orders.set(&open_orders);
+
+            let mut collateral: contract_ref!(PSP22) = collateral_asset.into();
+            collateral
+                .approve(self.vault, escrow_amount)
+                .map_err(|_| MarketError::ApproveFailed)?;
+
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .deposit(caller, id, collateral_asset, escrow_amount)
+                .map_err(|err| MarketError::VaultError(err))?;
+
+            self.new_id.insert(caller, &id.saturating_add(1));
+
+            self.emit_event(OrderCreated {
+                user: caller,
+                id,
+                collateral_asset,
+                collateral_amount,
+                execution_fee,
+                trigger_price,
+                leverage,
+                is_long,
+                block_expiry,
+            });
+
+            Ok(id)
+        }
+
+        // First block at which a request made at `block` can be claimed.
+        fn calculate_withdraw_block(&self, block: u32) -> Result<u32, MarketError> {
+            let block_claimable = block
+                .checked_add(self.withdraw_delay)
+                .ok_or(MarketError::Overflow(String::from("calculate_withdraw_block_1")))?;
+
+            if self.withdraw_epoch_length == 0 {
+                return Ok(block_claimable);
+            }
+
+            block_claimable
+                .checked_add(self.withdraw_epoch_length - 1)
+                .ok_or(MarketError::Overflow(String::from("calculate_withdraw_block_2")))?
+                .checked_div(self.withdraw_epoch_length)
+                .ok_or(MarketError::Overflow(String::from("calculate_withdraw_block_3")))?
+                .checked_mul(self.withdraw_epoch_length)
+                .ok_or(MarketError::Overflow(String::from("calculate_withdraw_block_4")))
+        }
+
+        fn remove_withdraw_request(&mut self, user: AccountId, id: u128) -> Result<WithdrawRequest, MarketError> {
+            let request = self
+                .withdraw_requests
+                .get((user, id))
+                .ok_or(MarketError::WithdrawRequestNotFound)?;
+
+            let mut withdraw_ids_for_user = self.withdraw_ids_per_user.get(user).unwrap_or_default();
+            if let Some(index_to_remove) = withdraw_ids_for_user.iter().position(|&x| x == id) {
+                withdraw_ids_for_user.swap_remove(index_to_remove);
+                self.withdraw_ids_per_user.insert(user, &withdraw_ids_for_user);
+            }
+
+            self.withdraw_requests.remove((user, id));
+
+            Ok(request)
+        }
+
+        // Redeems the locked shares at the share price of the claiming block.
+        fn claim_withdraw_request(&mut self, user: AccountId, id: u128) -> Result<u128, MarketError> {
+            let request = self
+                .withdraw_requests
+                .get((user, id))
+                .ok_or(MarketError::WithdrawRequestNotFound)?;
+
+            if !request.is_claimable(self.env().block_number()) {
+                return Err(MarketError::WithdrawLocked);
+            }
+
+            self.remove_withdraw_request(user, id)?;
+
+            self.burn_and_calculate_amount(user, request.shares)
+        }
+
+        fn remove_order(&mut self, user: AccountId, id: u128) -> Result<Order, MarketError> {
+            let order = self
+                .orders
+                .get((user, id))
+                .ok_or(MarketError::OrderNotFound)?;
+
+            let mut order_ids_for_user = self.order_ids_per_user.get(user).unwrap_or_default();
+            if let Some(index_to_remove) = order_ids_for_user.iter().position(|&x| x == id) {
+                order_ids_for_user.swap_remove(index_to_remove);
+                self.order_ids_per_user.insert(user, &order_ids_for_user);
+            }
+
+            self.orders.remove((user, id));
+            self.open_orders.set(&self.open_orders.get().unwrap_or_default().saturating_sub(1));
+
+            Ok(order)
+        }
+
+        fn settle_position(
+            &mut self,
+            position: &Position,
+            close_amount: Balance,
+            keeper: Option<AccountId>,
+        ) -> Result<(), MarketError> {
+            let contract = self.env().account_id();
+
+            let close_usd = self.calculate_close_usd(position, close_amount)?;
+
+            let underlying_asset_decimals = self.get_decimals(self.underlying_asset);
+            let underlying_price = self.fetch_price(self.underlying_asset)?;
+
+            let collateral_asset_decimals = self.get_decimals(position.collateral_asset);
+            let collateral_price = self.fetch_price(position.collateral_asset)?;
+
+            let funding_usd = self
+                .calculate_funding_usd(position)?
+                .checked_mul(close_amount as i128)
+                .ok_or(MarketError::Overflow(String::from("settle_position_1")))?
+                .checked_div(position.collateral_amount as i128)
+                .ok_or(MarketError::Overflow(String::from("settle_position_2")))?;
+
+            let pnl_usd = self
+                .calculate_pnl_usd(position, underlying_price)?
+                .checked_mul(close_amount as i128)
+                .ok_or(MarketError::Overflow(String::from("settle_position_3")))?
+                .checked_div(position.collateral_amount as i128)
+                .ok_or(MarketError::Overflow(String::from("settle_position_4")))?
+                .checked_sub(funding_usd)
+                .ok_or(MarketError::Overflow(String::from("settle_position_5")))?
+                .min(self.calculate_max_profit_usd(close_usd)? as i128);
+
+            let notional_usd = self.calculate_notional_usd(close_usd, position.leverage)?;
+            let reserved_amount = self.calculate_released_reserve(position, close_amount)?;
+
+            let fees_usd = self
+                .calculate_borrow_fee_usd(position)?
+                .checked_mul(close_amount)
+                .ok_or(MarketError::Overflow(String::from("settle_position_6")))?
+                .checked_div(position.collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("settle_position_7")))?
+                .checked_add(self.calculate_fee_usd(notional_usd, self.close_fee)?)
+                .ok_or(MarketError::Overflow(String::from("settle_position_8")))?;
+
+            let (fees_from_profit_usd, fees_from_collateral_usd, rest_collateral_usd) =
+                self.split_fees(pnl_usd, fees_usd, close_usd)?;
+
+            let fees_from_profit_amount = self.calculate_asset_amount_from_usd(
+                fees_from_profit_usd,
+                underlying_price,
+                underlying_asset_decimals,
+            )?;
+            self.accrue_protocol_fee(self.underlying_asset, fees_from_profit_amount)?;
+
+            let pnl_usd = pnl_usd
+                .checked_sub(fees_usd as i128)
+                .ok_or(MarketError::Overflow(String::from("settle_position_9")))?;
+
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            let mut user_collateral_amount = close_amount;
+
+            if pnl_usd > 0 {
+                let payout_amount = self
+                    .calculate_asset_amount_from_usd(
+                        pnl_usd as u128,
+                        underlying_price,
+                        underlying_asset_decimals,
+                    )?
+                    .min(reserved_amount);
+
+                let mut asset: contract_ref!(PSP22) = self.underlying_asset.into();
+                asset
+                    .transfer(position.user, payout_amount, Vec::new())
+                    .map_err(|_| MarketError::TransferFailed)?;
+            } else if pnl_usd < 0 {
+                user_collateral_amount = self.calculate_asset_amount_from_usd(
+                    rest_collateral_usd,
+                    collateral_price,
+                    collateral_asset_decimals,
+                )?;
+
+                let collateral_for_contract: u128 = close_amount
+                    .checked_sub(user_collateral_amount)
+                    .ok_or(MarketError::Overflow(String::from("settle_position_10")))?;
+
+                if collateral_for_contract > 0 {
+                    vault
+                        .withdraw(position.user, position.id, collateral_for_contract, contract)
+                        .map_err(|err| MarketError::VaultError(err))?;
+                }
+
+                let fees_from_collateral_amount = self
+                    .calculate_asset_amount_from_usd(
+                        fees_from_collateral_usd,
+                        collateral_price,
+                        collateral_asset_decimals,
+                    )?
+                    .min(collateral_for_contract);
+                self.accrue_protocol_fee(position.collateral_asset, fees_from_collateral_amount)?;
+            }
+
+            if let Some(keeper) = keeper {
+                let keeper_fee_amount = user_collateral_amount
+                    .checked_mul(self.trigger_fee as u128)
+                    .ok_or(MarketError::Overflow(String::from("settle_position_11")))?
+                    .checked_div(BASIS_POINTS)
+                    .ok_or(MarketError::Overflow(String::from("settle_position_12")))?;
+
+                if keeper_fee_amount > 0 {
+                    vault
+                        .withdraw(position.user, position.id, keeper_fee_amount, keeper)
+                        .map_err(|err| MarketError::VaultError(err))?;
+
+                    user_collateral_amount = user_collateral_amount
+                        .checked_sub(keeper_fee_amount)
+                        .ok_or(MarketError::Overflow(String::from("settle_position_13")))?;
+                }
+            }
+
+            if user_collateral_amount > 0 {
+                vault
+                    .withdraw(position.user, position.id, user_collateral_amount, position.user)
+                    .map_err(|err| MarketError::VaultError(err))?;
+            }
+
+            self.decrease_open_interest(notional_usd, position.entry_price, position.is_long, reserved_amount)?;
+
+            self.emit_event(PositionClosed {
+                user: position.user,
+                id: position.id,
+                collateral_amount: close_amount,
+                pnl: pnl_usd,
+                keeper,
+            });
+
+            Ok(())
+        }
+
+        // Collateral value backing `close_amount` of the position's collateral.
+        fn calculate_close_usd(&self, position: &Position, close_amount: Balance) -> Result<u128, MarketError> {
+            position.collateral_usd
+                .checked_mul(close_amount)
+                .ok_or(MarketError::Overflow(String::from("calculate_close_usd_1")))?
+                .checked_div(position.collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("calculate_close_usd_2")))
+        }
+
+        // Fees are collected in underlying by paying out less profit, and from the collateral
+        // for the part the profit doesn't cover. Returns the fees taken from profit and from
+        // collateral, and the collateral value left to the user, all in USD.
+        fn split_fees(
+            &self,
+            pnl_usd: i128,
+            fees_usd: u128,
+            close_usd: u128,
+        ) -> Result<(u128, u128, u128), MarketError> {
+            let fees_from_profit_usd = pnl_usd.clamp(0, fees_usd as i128) as u128;
+
+            let net_pnl_usd = pnl_usd
+                .checked_sub(fees_usd as i128)
+                .ok_or(MarketError::Overflow(String::from("split_fees_1")))?;
+
+            if net_pnl_usd >= 0 {
+                return Ok((fees_from_profit_usd, 0, close_usd));
+            }
+
+            let rest_collateral_usd = (close_usd as i128)
+                .checked_add(net_pnl_usd)
+                .ok_or(MarketError::Overflow(String::from("split_fees_2")))?
+                .max(0) as u128;
+
+            let fees_from_collateral_usd = fees_usd
+                .saturating_sub(fees_from_profit_usd)
+                .min(close_usd.saturating_sub(rest_collateral_usd));
+
+            Ok((fees_from_profit_usd, fees_from_collateral_usd, rest_collateral_usd))
+        }
+
+        // Takes `close_amount` of collateral out of the position with its share of the
+        // collateral value and reserve. What stays open has to meet the same minimum as a
+        // new position.
+        fn reduce_position(&self, position: &mut Position, close_amount: Balance) -> Result<(), MarketError> {
+            let closed_collateral_usd = self.calculate_close_usd(position, close_amount)?;
+            let released_reserve = self.calculate_released_reserve(position, close_amount)?;
+
+            position.collateral_amount = position.collateral_amount
+                .checked_sub(close_amount)
+                .ok_or(MarketError::Overflow(String::from("reduce_position_1")))?;
+            position.collateral_usd = position.collateral_usd
+                .checked_sub(closed_collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("reduce_position_2")))?;
+            position.reserved_amount = position.reserved_amount
+                .checked_sub(released_reserve)
+                .ok_or(MarketError::Overflow(String::from("reduce_position_3")))?;
+
+            self.validate_position_size(position.collateral_usd, position.leverage)
+        }
+
+        fn increase_collateral(
+            &mut self,
+            caller: AccountId,
+            id: u128,
+            collateral_amount: Balance,
+        ) -> Result<(), MarketError> {
+            let mut position = self
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            self.accrue_funding()?;
+
+            let collateral_decimals = self.get_decimals(position.collateral_asset);
+            let collateral_price = self.fetch_price(position.collateral_asset)?;
+
+            let added_collateral_usd = self.calculate_usd_from_asset_amount(
+                collateral_amount,
+                collateral_decimals,
+                collateral_price,
+            )?;
+
+            let collateral_usd = position.collateral_usd
+                .checked_add(added_collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("increase_collateral_1")))?;
+
+            position.collateral_amount = position.collateral_amount
+                .checked_add(collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("increase_collateral_2")))?;
+
+            self.update_margin(&mut position, collateral_usd)?;
+
+            let mut collateral: contract_ref!(PSP22) = position.collateral_asset.into();
+            collateral
+                .approve(self.vault, collateral_amount)
+                .map_err(|_| MarketError::ApproveFailed)?;
+
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .deposit(caller, id, position.collateral_asset, collateral_amount)
+                .map_err(|err| MarketError::VaultError(err))?;
+
+            self.insert_position(&position);
+            self.emit_position_updated(&position);
+
+            Ok(())
+        }
+
+        fn merge_into_position(
+            &mut self,
+            caller: AccountId,
+            id: u128,
+            collateral_amount: Balance,
+            leverage: u32,
+        ) -> Result<(), MarketError> {
+            let mut position = self
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            self.validate_leverage(leverage)?;
+
+            let mut collateral: contract_ref!(PSP22) = position.collateral_asset.into();
+            collateral
+                .approve(self.vault, collateral_amount)
+                .map_err(|_| MarketError::ApproveFailed)?;
+
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .deposit(caller, id, position.collateral_asset, collateral_amount)
+                .map_err(|err| MarketError::VaultError(err))?;
+
+            self.accrue_funding()?;
+
+            let collateral_decimals = self.get_decimals(position.collateral_asset);
+            let collateral_price = self.fetch_price(position.collateral_asset)?;
+
+            let added_collateral_usd = self.calculate_usd_from_asset_amount(
+                collateral_amount,
+                collateral_decimals,
+                collateral_price,
+            )?;
+
+            let (collateral_amount, added_collateral_usd) = self.charge_open_fee(
+                caller,
+                id,
+                position.collateral_asset,
+                collateral_amount,
+                added_collateral_usd,
+                leverage,
+            )?;
+
+            let current_price = self.fetch_price(self.underlying_asset)?;
+
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+            let added_notional_usd = self.calculate_notional_usd(added_collateral_usd, leverage)?;
+
+            let (entry_price, entry_funding_index, entry_borrow_index) = self.calculate_merged_entry(
+                &position,
+                added_notional_usd,
+                added_collateral_usd,
+                current_price,
+            )?;
+
+            let merged_notional_usd = notional_usd
+                .checked_add(added_notional_usd)
+                .ok_or(MarketError::Overflow(String::from("merge_into_position_1")))?;
+            let collateral_usd = position.collateral_usd
+                .checked_add(added_collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("merge_into_position_2")))?;
+
+            let merged_leverage = self.calculate_leverage(merged_notional_usd, collateral_usd)?;
+            self.validate_position_size(collateral_usd, merged_leverage)?;
+
+            self.decrease_open_interest(
+                notional_usd,
+                position.entry_price,
+                position.is_long,
+                position.reserved_amount,
+            )?;
+            position.reserved_amount = self.increase_open_interest(
+                self.calculate_notional_usd(collateral_usd, merged_leverage)?,
+                collateral_usd,
+                entry_price,
+                position.is_long,
+            )?;
+
+            position.collateral_amount = position.collateral_amount
+                .checked_add(collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("merge_into_position_3")))?;
+            position.collateral_usd = collateral_usd;
+            position.entry_price = entry_price;
+            position.leverage = merged_leverage;
+            position.entry_funding_index = entry_funding_index;
+            position.entry_borrow_index = entry_borrow_index;
+            position.liquidation_price =
+                self.calculate_liquidation_price(entry_price, merged_leverage, position.is_long)?;
+
+            if self.is_below_liquidation_threshold(&position, current_price)? {
+                return Err(MarketError::InsufficientMargin);
+            }
+
+            self.insert_position(&position);
+            self.emit_position_updated(&position);
+
+            Ok(())
+        }
+
+        // Entry price of the merged position, weighted by size, and entry indexes moved so the
+        // funding and borrow fees already owed by the existing position are carried over.
+        fn calculate_merged_entry(
+            &self,
+            position: &Position,
+            added_notional_usd: u128,
+            added_collateral_usd: u128,
+            current_price: u128,
+        ) -> Result<(u128, i128, u128), MarketError> {
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+
+            let size = self.calculate_size(notional_usd, position.entry_price)?;
+            let added_size = self.calculate_size(added_notional_usd, current_price)?;
+
+            let merged_notional_usd = notional_usd
+                .checked_add(added_notional_usd)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_1")))?;
+
+            let entry_price = merged_notional_usd
+                .checked_mul(SIZE_PRECISION)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_2")))?
+                .checked_div(
+                    size.checked_add(added_size)
+                        .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_3")))?
+                )
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_4")))?;
+
+            // funding accrues on notional
+            let funding_index_delta = self.funding_index
+                .checked_sub(position.entry_funding_index)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_5")))?
+                .checked_mul(notional_usd as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_6")))?
+                .checked_div(merged_notional_usd as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_7")))?;
+
+            let collateral_usd = position.collateral_usd
+                .checked_add(added_collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_8")))?;
+
+            // borrow fees accrue on the reserve
+            let borrow_index_delta = self.borrow_index
+                .checked_sub(position.entry_borrow_index)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_9")))?
+                .checked_mul(self.calculate_reserve_usd(notional_usd, position.collateral_usd)?)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_10")))?
+                .checked_div(self.calculate_reserve_usd(merged_notional_usd, collateral_usd)?)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_11")))?;
+
+            let entry_funding_index = self.funding_index
+                .checked_sub(funding_index_delta)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_12")))?;
+            let entry_borrow_index = self.borrow_index
+                .checked_sub(borrow_index_delta)
+                .ok_or(MarketError::Overflow(String::from("calculate_merged_entry_13")))?;
+
+            Ok((entry_price, entry_funding_index, entry_borrow_index))
+        }
+
+        // Keeps the position's notional while re-basing it on `collateral_usd`, then
+        // recomputes leverage and liquidation price and checks the new margin.
+        fn update_margin(
+            &mut self,
+            position: &mut Position,
+            collateral_usd: u128,
+        ) -> Result<(), MarketError> {
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+            let leverage = self.calculate_leverage(notional_usd, collateral_usd)?;
+
+            if leverage > self.max_leverage {
+                return Err(MarketError::LeverageTooHigh);
+            }
+
+            // the notional is unchanged, so open interest and caps are left alone and topping up
+            // margin is possible even when the pool is over its caps; only the reserve follows
+            // the max profit of the new collateral, without a utilisation check
+            let reserved_amount =
+                self.calculate_reserve_amount(notional_usd, collateral_usd, position.entry_price)?;
+            self.reserved_amount = self.reserved_amount
+                .saturating_sub(position.reserved_amount)
+                .checked_add(reserved_amount)
+                .ok_or(MarketError::Overflow(String::from("update_margin")))?;
+            position.reserved_amount = reserved_amount;
+
+            position.collateral_usd = collateral_usd;
+            position.leverage = leverage;
+            position.liquidation_price =
+                self.calculate_liquidation_price(position.entry_price, leverage, position.is_long)?;
+
+            let current_price = self.fetch_price(self.underlying_asset)?;
+
+            if self.is_below_liquidation_threshold(position, current_price)? {
+                return Err(MarketError::InsufficientMargin);
+            }
+
+            Ok(())
+        }
+
+        // Native AZERO is wrapped into WAZERO, so it can only top up positions backed by WAZERO.
+        fn ensure_native_collateral(&self, user: AccountId, id: u128) -> Result<(), MarketError> {
+            let position = self
+                .get_position(user, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            if position.collateral_asset != self.wazero {
+                return Err(MarketError::NotSupported);
+            }
+
+            Ok(())
+        }
+
+        fn ensure_not_paused(&self, scope: PauseScope) -> Result<(), MarketError> {
+            if self.paused_scopes.contains(&scope) {
+                return Err(MarketError::Paused);
+            }
+
+            if matches!(scope, PauseScope::Open | PauseScope::Deposit) && self.is_deprecated() {
+                return Err(MarketError::Deprecated);
+            }
+
+            Ok(())
+        }
+
+        fn remove_config_change(&mut self, id: u128) {
+            self.config_changes.remove(id);
+
+            if let Some(index_to_remove) = self.config_change_ids.iter().position(|&x| x == id) {
+                self.config_change_ids.swap_remove(index_to_remove);
+            }
+        }
+
+        fn validate_leverage(&self, leverage: u32) -> Result<(), MarketError> {
+            if leverage < self.min_leverage {
+                return Err(MarketError::LeverageTooLow);
+            }
+
+            if leverage > self.max_leverage {
+                return Err(MarketError::LeverageTooHigh);
+            }
+
+            Ok(())
+        }
+
+        fn validate_position_size(&self, collateral_usd: u128, leverage: u32) -> Result<(), MarketError> {
+            if collateral_usd < self.min_collateral_usd {
+                return Err(MarketError::CollateralTooLow);
+            }
+
+            if self.calculate_notional_usd(collateral_usd, leverage)? > self.max_position_notional {
+                return Err(MarketError::PositionTooLarge);
+            }
+
+            Ok(())
+        }
+
+        fn calculate_liquidation_price(
+            &self,
+            entry_price: u128,
+            leverage: u32,
+            is_long: bool, 
+        ) -> Result<u128, MarketError> {
+            let sign: i128 = if is_long { 1 } else { -1 };
+            let result: i128 = (entry_price as i128)
+                .checked_mul(self.liquidation_threshold as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_1")))?
+                .checked_div(leverage as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_2")))?
+                .checked_mul(sign)
+                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_3")))?
+                .checked_add(entry_price as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_liquidation_price_4")))?;
+
+            Ok(result as u128)
+        }
+
+        fn calculate_notional_usd(
+            &self,
+            collateral_usd: u128,
+            leverage: u32,
+        ) -> Result<u128, MarketError> {
+            collateral_usd
+                .checked_mul(leverage as u128)
+                .ok_or(MarketError::Overflow(String::from("calculate_notional_usd_1")))?
+                .checked_div(BASIS_POINTS)
+                .ok_or(MarketError::Overflow(String::from("calculate_notional_usd_2")))
+        }
+
+        fn calculate_funding_rate(&self) -> Result<i128, MarketError> {
+            let total_open_interest = self.long_open_interest
+                .checked_add(self.short_open_interest)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_rate_1")))?;
+
+            if total_open_interest == 0 {
+                return Ok(0);
+            }
+
+            (self.long_open_interest as i128)
+                .checked_sub(self.short_open_interest as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_rate_2")))?
+                .checked_mul(self.funding_rate_factor as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_rate_3")))?
+                .checked_div(total_open_interest as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_rate_4")))
+        }
+
+        fn calculate_funding_index(&self) -> Result<i128, MarketError> {
+            let elapsed_blocks = self.env()
+                .block_number()
+                .saturating_sub(self.last_funding_block);
+
+            self.calculate_funding_rate()?
+                .checked_mul(elapsed_blocks as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_index_1")))?
+                .checked_add(self.funding_index)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_index_2")))
+        }
+
+        fn calculate_borrow_index(&self) -> Result<u128, MarketError> {
+            let elapsed_blocks = self.env()
+                .block_number()
+                .saturating_sub(self.last_funding_block);
+
+            (self.borrow_fee as u128)
+                .checked_mul(elapsed_blocks as u128)
+                .ok_or(MarketError::Overflow(String::from("calculate_borrow_index_1")))?
+                .checked_add(self.borrow_index)
+                .ok_or(MarketError::Overflow(String::from("calculate_borrow_index_2")))
+        }
+
+        // Accrues funding and borrow fees up to the current block.
+        fn accrue_funding(&mut self) -> Result<(), MarketError> {
+            self.funding_index = self.calculate_funding_index()?;
+            self.borrow_index = self.calculate_borrow_index()?;
+            self.last_funding_block = self.env().block_number();
+
+            Ok(())
+        }
+
+        fn calculate_funding_usd(&self, position: &Position) -> Result<i128, MarketError> {
+            let sign: i128 = if position.is_long { 1 } else { -1 };
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+
+            self.calculate_funding_index()?
+                .checked_sub(position.entry_funding_index)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_usd_1")))?
+                .checked_mul(notional_usd as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_usd_2")))?
+                .checked_mul(sign)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_usd_3")))?
+                .checked_div(FUNDING_PRECISION)
+                .ok_or(MarketError::Overflow(String::from("calculate_funding_usd_4")))
+        }
+
+        fn calculate_borrow_fee_usd(&self, position: &Position) -> Result<u128, MarketError> {
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+            let reserve_usd = self.calculate_reserve_usd(notional_usd, position.collateral_usd)?;
+
+            self.calculate_borrow_index()?
+                .checked_sub(position.entry_borrow_index)
+                .ok_or(MarketError::Overflow(String::from("calculate_borrow_fee_usd_1")))?
+                .checked_mul(reserve_usd)
+                .ok_or(MarketError::Overflow(String::from("calculate_borrow_fee_usd_2")))?
+                .checked_div(
+                    BASIS_POINTS
+                        .checked_mul(BLOCKS_PER_HOUR)
+                        .ok_or(MarketError::Overflow(String::from("calculate_borrow_fee_usd_3")))?
+                )
+                .ok_or(MarketError::Overflow(String::from("calculate_borrow_fee_usd_4")))
+        }
+
+        fn calculate_fee_usd(&self, notional_usd: u128, fee: u16) -> Result<u128, MarketError> {
+            notional_usd
+                .checked_mul(fee as u128)
+                .ok_or(MarketError::Overflow(String::from("calculate_fee_usd_1")))?
+                .checked_div(BASIS_POINTS)
+                .ok_or(MarketError::Overflow(String::from("calculate_fee_usd_2")))
+        }
+
+        // Owner's cut of a fee, the remainder stays in the market for LPs.
+        fn accrue_protocol_fee(&mut self, asset: AccountId, fee_amount: u128) -> Result<(), MarketError> {
+            let protocol_amount = fee_amount
+                .checked_mul(self.protocol_fee_share as u128)
+                .ok_or(MarketError::Overflow(String::from("accrue_protocol_fee_1")))?
+                .checked_div(BASIS_POINTS)
+                .ok_or(MarketError::Overflow(String::from("accrue_protocol_fee_2")))?;
+
+            let protocol_fees = self.protocol_fees
+                .get(asset)
+                .unwrap_or_default()
+                .checked_add(protocol_amount)
+                .ok_or(MarketError::Overflow(String::from("accrue_protocol_fee_3")))?;
+            self.protocol_fees.insert(asset, &protocol_fees);
+
+            Ok(())
+        }
+
+        // Takes the open fee on the requested notional out of collateral already in the
+        // vault and returns the remaining (collateral_amount, collateral_usd).
+        fn charge_open_fee(
+            &mut self,
+            user: AccountId,
+            id: u128,
+            collateral_asset: AccountId,
+            collateral_amount: Balance,
+            collateral_usd: u128,
+            leverage: u32,
+        ) -> Result<(u128, u128), MarketError> {
+            let notional_usd = self.calculate_notional_usd(collateral_usd, leverage)?;
+            let fee_usd = self.calculate_fee_usd(notional_usd, self.open_fee)?;
+
+            if fee_usd == 0 {
+                return Ok((collateral_amount, collateral_usd));
+            }
+
+            if fee_usd >= collateral_usd {
+                return Err(MarketError::InsufficientMargin);
+            }
+
+            let fee_amount = collateral_amount
+                .checked_mul(fee_usd)
+                .ok_or(MarketError::Overflow(String::from("charge_open_fee_1")))?
+                .checked_div(collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("charge_open_fee_2")))?;
+
+            let contract = self.env().account_id();
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .withdraw(user, id, fee_amount, contract)
+                .map_err(|err| MarketError::VaultError(err))?;
+
+            self.accrue_protocol_fee(collateral_asset, fee_amount)?;
+
+            Ok((
+                collateral_amount
+                    .checked_sub(fee_amount)
+                    .ok_or(MarketError::Overflow(String::from("charge_open_fee_3")))?,
+                collateral_usd
+                    .checked_sub(fee_usd)
+                    .ok_or(MarketError::Overflow(String::from("charge_open_fee_4")))?,
+            ))
+        }
+
+        fn calculate_size(&self, notional_usd: u128, entry_price: u128) -> Result<u128, MarketError> {
+            notional_usd
+                .checked_mul(SIZE_PRECISION)
+                .ok_or(MarketError::Overflow(String::from("calculate_size_1")))?
+                .checked_div(entry_price)
+                .ok_or(MarketError::Overflow(String::from("calculate_size_2")))
+        }
+
+        // A position can't be paid more than its max profit, so only that much is reserved when
+        // it is below the notional.
+        fn calculate_reserve_usd(&self, notional_usd: u128, collateral_usd: u128) -> Result<u128, MarketError> {
+            Ok(notional_usd.min(self.calculate_max_profit_usd(collateral_usd)?))
+        }
+
+        fn calculate_reserve_amount(
+            &self,
+            notional_usd: u128,
+            collateral_usd: u128,
+            entry_price: u128,
+        ) -> Result<u128, MarketError> {
+            self.calculate_asset_amount_from_usd(
+                self.calculate_reserve_usd(notional_usd, collateral_usd)?,
+                entry_price,
+                self.get_decimals(self.underlying_asset),
+            )
+        }
+
+        // Share of the position's reserve backing `close_amount` of its collateral.
+        fn calculate_released_reserve(&self, position: &Position, close_amount: Balance) -> Result<u128, MarketError> {
+            position.reserved_amount
+                .checked_mul(close_amount)
+                .ok_or(MarketError::Overflow(String::from("calculate_released_reserve_1")))?
+                .checked_div(position.collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("calculate_released_reserve_2")))
+        }
+
+        // Returns the amount reserved for the position, to be released by `decrease_open_interest`.
+        fn increase_open_interest(
+            &mut self,
+            notional_usd: u128,
+            collateral_usd: u128,
+            entry_price: u128,
+            is_long: bool,
+        ) -> Result<u128, MarketError> {
+            let size = self.calculate_size(notional_usd, entry_price)?;
+
+            let reserve_amount = self.calculate_reserve_amount(notional_usd, collateral_usd, entry_price)?;
+            let reserved_amount = self.reserved_amount
+                .checked_add(reserve_amount)
+                .ok_or(MarketError::Overflow(String::from("increase_open_interest_1")))?;
+
+            let max_reserved_amount = self
+                .calculate_liquidity()
+                .checked_mul(self.max_utilisation as u128)
+                .ok_or(MarketError::Overflow(String::from("increase_open_interest_2")))?
+                .checked_div(BASIS_POINTS)
+                .ok_or(MarketError::Overflow(String::from("increase_open_interest_3")))?;
+
+            if reserved_amount > max_reserved_amount {
+                return Err(MarketError::UtilisationExceeded);
+            }
+
+            self.reserved_amount = reserved_amount;
+
+            if is_long {
+                let long_open_interest = self.long_open_interest
+                    .checked_add(notional_usd)
+                    .ok_or(MarketError::Overflow(String::from("increase_open_interest_4")))?;
+
+                if long_open_interest > self.max_long_open_interest {
+                    return Err(MarketError::OpenInterestCapExceeded);
+                }
+
+                self.long_open_interest = long_open_interest;
+                self.long_open_size = self.long_open_size
+                    .checked_add(size)
+                    .ok_or(MarketError::Overflow(String::from("increase_open_interest_5")))?;
+            } else {
+                let short_open_interest = self.short_open_interest
+                    .checked_add(notional_usd)
+                    .ok_or(MarketError::Overflow(String::from("increase_open_interest_6")))?;
+
+                if short_open_interest > self.max_short_open_interest {
+                    return Err(MarketError::OpenInterestCapExceeded);
+                }
+
+                self.short_open_interest = short_open_interest;
+                self.short_open_size = self.short_open_size
+                    .checked_add(size)
+                    .ok_or(MarketError::Overflow(String::from("increase_open_interest_7")))?;
+            }
+
+            Ok(reserve_amount)
+        }
+
+        fn decrease_open_interest(
+            &mut self,
+            notional_usd: u128,
+            entry_price: u128,
+            is_long: bool,
+            reserved_amount: u128,
+        ) -> Result<(), MarketError> {
+            let size = self.calculate_size(notional_usd, entry_price)?;
+
+            self.reserved_amount = self.reserved_amount.saturating_sub(reserved_amount);
+
+            if is_long {
+                self.long_open_interest = self.long_open_interest.saturating_sub(notional_usd);
+                self.long_open_size = self.long_open_size.saturating_sub(size);
+            } else {
+                self.short_open_interest = self.short_open_interest.saturating_sub(notional_usd);
+                self.short_open_size = self.short_open_size.saturating_sub(size);
+            }
+
+            Ok(())
+        }
+
+        // Unrealised price PnL of all open positions in USD, positive when traders are in profit.
+        fn calculate_unrealised_pnl_usd(&self, price: u128) -> Result<i128, MarketError> {
+            let long_value = price
+                .checked_mul(self.long_open_size)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_1")))?
+                .checked_div(SIZE_PRECISION)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_2")))?;
+
+            let short_value = price
+                .checked_mul(self.short_open_size)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_3")))?
+                .checked_div(SIZE_PRECISION)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_4")))?;
+
+            (long_value as i128)
+                .checked_sub(self.long_open_interest as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_5")))?
+                .checked_add(self.short_open_interest as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_6")))?
+                .checked_sub(short_value as i128)
+                .ok_or(MarketError::Overflow(String::from("calculate_unrealised_pnl_usd_7")))
+        }
+
+        // Underlying held by the market net of what it owes (or is owed by) open positions.
+        fn calculate_pool_value(&self) -> Result<u128, MarketError> {
+            let balance = self.calculate_liquidity();
+
+            if self.long_open_interest == 0 && self.short_open_interest == 0 {
+                return Ok(balance);
+            }
+
+            let price = self.get_price(self.underlying_asset)?;
+            let unrealised_pnl_usd = self.calculate_unrealised_pnl_usd(price)?;
+            let unrealised_pnl_amount = self.calculate_asset_amount_from_usd(
+                unrealised_pnl_usd.unsigned_abs(),
+                price,
+                self.get_decimals(self.underlying_asset),
+            )?;
+
+            if unrealised_pnl_usd > 0 {
+                Ok(balance.saturating_sub(unrealised_pnl_amount))
+            } else {
+                balance
+                    .checked_add(unrealised_pnl_amount)
+                    .ok_or(MarketError::Overflow(String::from("calculate_pool_value")))
+            }
+        }
+
+        #[ink(message, payable)]
+        pub fn deposit_native(&mut self) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Deposit)?;
+
+            let caller = self.env().caller();
+
+            let transferred_amount = self.env().transferred_value();
+            self.wrap_native(transferred_amount)?;
+
+            self.calculate_amount_and_mint(caller, transferred_amount)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn deposit(&mut self, amount: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Deposit)?;
+
+            let caller = self.env().caller();
+            let contract = self.env().account_id();
+
+            let mut underlying_asset: contract_ref!(PSP22) = self.underlying_asset.into();
+            underlying_asset
+                .transfer_from(caller, contract, amount, Vec::new())
+                .map_err(|_| MarketError::TransferFailed)?;
+
+            self.calculate_amount_and_mint(caller, amount)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn request_withdraw(&mut self, deposit_token_amount: u128) -> Result<u128, MarketError> {
+            self.ensure_not_paused(PauseScope::Withdraw)?;
+
+            let caller = self.env().caller();
+            let contract = self.env().account_id();
+
+            if deposit_token_amount == 0 {
+                return Err(MarketError::InvalidAmount);
+            }
+
+            let events = self.data.transfer(caller, contract, deposit_token_amount)
+                .map_err(MarketError::PSP22Error)?;
+            self.emit_events(events);
+
+            let block = self.env().block_number();
+            let block_claimable = self.calculate_withdraw_block(block)?;
+
+            let id = self.new_withdraw_id.get(caller).unwrap_or_default();
+            let request = WithdrawRequest::new(caller, id, deposit_token_amount, block, block_claimable);
+            self.withdraw_requests.insert((caller, id), &request);
+
+            let mut withdraw_ids_for_user = self.withdraw_ids_per_user.get(caller).unwrap_or_default();
+            withdraw_ids_for_user.push(id);
+            self.withdraw_ids_per_user.insert(caller, &withdraw_ids_for_user);
+
+            let new_id = id
+                .checked_add(1)
+                .ok_or(MarketError::Overflow(String::from("request_withdraw")))?;
+            self.new_withdraw_id.insert(caller, &new_id);
+
+            self.emit_event(WithdrawRequested {
+                provider: caller,
+                id,
+                shares: deposit_token_amount,
+                block_claimable,
+            });
+
+            Ok(id)
+        }
+
+        #[ink(message)]
+        pub fn cancel_withdraw(&mut self, id: u128) -> Result<(), MarketError> {
             let caller = self.env().caller();
             let contract = self.env().account_id();
 
-            let mut underlying_asset: contract_ref!(PSP22) = self.underlying_asset.into();
-            underlying_asset
-                .transfer_from(caller, contract, amount, Vec::new())
-                .map_err(|_| MarketError::TransferFailed)?;
+            let request = self.remove_withdraw_request(caller, id)?;
 
-            self.calculate_amount_and_mint(caller, amount)?;
+            let events = self.data.transfer(contract, caller, request.shares)
+                .map_err(MarketError::PSP22Error)?;
+            self.emit_events(events);
+
+            self.emit_event(WithdrawCancelled {
+                provider: caller,
+                id,
+            });
 
             Ok(())
         }
 
         #[ink(message)]
-        pub fn withdraw_native(&mut self, deposit_token_amount: u128) -> Result<(), MarketError> {
+        pub fn withdraw_native(&mut self, id: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Withdraw)?;
+
             let caller = self.env().caller();
 
-            let token_amount = self.burn_and_calculate_amount(caller, deposit_token_amount)?;
+            let token_amount = self.claim_withdraw_request(caller, id)?;
 
             let mut wazero: contract_ref!(WrappedAZERO) = self.underlying_asset.into();
             wazero
@@ -466,10 +2543,12 @@ pub mod market {
         }
 
         #[ink(message)]
-        pub fn withdraw(&mut self, deposit_token_amount: u128) -> Result<(), MarketError> {
+        pub fn withdraw(&mut self, id: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Withdraw)?;
+
             let caller = self.env().caller();
 
-            let token_amount = self.burn_and_calculate_amount(caller, deposit_token_amount)?;
+            let token_amount = self.claim_withdraw_request(caller, id)?;
 
             let mut underlying_asset: contract_ref!(PSP22) = self.underlying_asset.into();
             underlying_asset
@@ -479,14 +2558,68 @@ pub mod market {
             Ok(())
         }
 
+        #[ink(message)]
+        pub fn view_withdraw_request(&self, user: AccountId, id: u128) -> Option<WithdrawRequest> {
+            self.withdraw_requests.get((user, id))
+        }
+
+        #[ink(message)]
+        pub fn view_withdraw_requests(&self, user: AccountId) -> Vec<WithdrawRequest> {
+            let withdraw_ids_for_user = self.withdraw_ids_per_user.get(user).unwrap_or_default();
+            let mut requests = Vec::new();
+
+            for id in withdraw_ids_for_user {
+                if let Some(request) = self.withdraw_requests.get((user, id)) {
+                    requests.push(request);
+                }
+            }
+
+            requests
+        }
+
+        #[ink(message)]
+        pub fn view_withdraw_delay(&self) -> (u32, u32) {
+            (self.withdraw_delay, self.withdraw_epoch_length)
+        }
+
+        #[ink(message)]
+        pub fn set_withdraw_delay(
+            &mut self,
+            withdraw_delay: u32,
+            withdraw_epoch_length: u32,
+        ) -> Result<(), MarketError> {
+            self.ensure_role(Role::Admin)?;
+
+            self.withdraw_delay = withdraw_delay;
+            self.withdraw_epoch_length = withdraw_epoch_length;
+
+            Ok(())
+        }
+
         #[ink(message, payable)]
-        pub fn open_native(&mut self, is_long: bool, leverage: u8) -> Result<(), MarketError> {
+        pub fn open_native(
+            &mut self,
+            is_long: bool,
+            leverage: u32,
+            take_profit: Option<u128>,
+            stop_loss: Option<u128>,
+        ) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
             let caller = self.env().caller();
 
             let collateral_amount = self.env().transferred_value();
             self.wrap_native(collateral_amount)?;
 
-            self.open_position(self.wazero, collateral_amount, is_long, leverage, caller)?;
+            self.open_position(
+                self.wazero,
+                collateral_amount,
+                is_long,
+                leverage,
+                take_profit,
+                stop_loss,
+                caller,
+            )?;
 
             Ok(())
         }
@@ -497,8 +2630,12 @@ pub mod market {
             collateral_asset: AccountId,
             collateral_amount: Balance,
             is_long: bool,
-            leverage: u8,
+            leverage: u32,
+            take_profit: Option<u128>,
+            stop_loss: Option<u128>,
         ) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
             let caller = self.env().caller();
             let contract = self.env().account_id();
 
@@ -512,6 +2649,8 @@ pub mod market {
                 collateral_amount,
                 is_long,
                 leverage,
+                take_profit,
+                stop_loss,
                 caller,
             )?;
 
@@ -519,13 +2658,212 @@ pub mod market {
         }
 
         #[ink(message)]
-        pub fn close(&mut self, id: u128) -> Result<(), MarketError> {
+        pub fn view_order(&self, user: AccountId, id: u128) -> Option<Order> {
+            self.orders.get((user, id))
+        }
+
+        #[ink(message)]
+        pub fn view_orders(&self, user: AccountId) -> Vec<Order> {
+            let order_ids_for_user = self.order_ids_per_user.get(user).unwrap_or_default();
+            let mut orders = Vec::new();
+
+            for id in order_ids_for_user {
+                if let Some(order) = self.orders.get((user, id)) {
+                    orders.push(order);
+                }
+            }
+
+            orders
+        }
+
+        #[ink(message, payable)]
+        pub fn create_order_native(
+            &mut self,
+            execution_fee: Balance,
+            trigger_price: u128,
+            is_long: bool,
+            leverage: u32,
+            block_expiry: u32,
+        ) -> Result<u128, MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
+            let caller = self.env().caller();
+
+            let transferred_amount = self.env().transferred_value();
+            self.wrap_native(transferred_amount)?;
+
+            let collateral_amount = transferred_amount
+                .checked_sub(execution_fee)
+                .ok_or(MarketError::InvalidAmount)?;
+
+            self.place_order(
+                caller,
+                self.wazero,
+                collateral_amount,
+                execution_fee,
+                trigger_price,
+                is_long,
+                leverage,
+                block_expiry,
+            )
+        }
+
+        /// Places a limit order that any keeper can execute for `execution_fee` once the
+        /// market price reaches `trigger_price`, until `block_expiry`.
+        #[ink(message)]
+        pub fn create_order(
+            &mut self,
+            collateral_asset: AccountId,
+            collateral_amount: Balance,
+            execution_fee: Balance,
+            trigger_price: u128,
+            is_long: bool,
+            leverage: u32,
+            block_expiry: u32,
+        ) -> Result<u128, MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
+            let caller = self.env().caller();
+            let contract = self.env().account_id();
+
+            let escrow_amount = collateral_amount
+                .checked_add(execution_fee)
+                .ok_or(MarketError::Overflow(String::from("create_order")))?;
+
+            let mut collateral: contract_ref!(PSP22) = collateral_asset.into();
+            collateral
+                .transfer_from(caller, contract, escrow_amount, Vec::new())
+                .map_err(|_| MarketError::TransferFailed)?;
+
+            self.place_order(
+                caller,
+                collateral_asset,
+                collateral_amount,
+                execution_fee,
+                trigger_price,
+                is_long,
+                leverage,
+                block_expiry,
+            )
+        }
+
+        #[ink(message)]
+        pub fn cancel_order(&mut self, id: u128) -> Result<(), MarketError> {
+            let caller = self.env().caller();
+
+            let order = self.remove_order(caller, id)?;
+
+            let escrow_amount = order.collateral_amount
+                .checked_add(order.execution_fee)
+                .ok_or(MarketError::Overflow(String::from("cancel_order")))?;
+
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .withdraw(caller, id, escrow_amount, caller)
+                .map_err(|err| MarketError::VaultError(err))?;
+
+            self.emit_event(OrderCancelled { user: caller, id });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn execute_order(&mut self, user: AccountId, id: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
+            let caller = self.env().caller();
+
+            let order = self.remove_order(user, id)?;
+
+            if self.env().block_number() > order.block_expiry {
+                return Err(MarketError::OrderExpired);
+            }
+
+            let current_price = self.fetch_price(self.underlying_asset)?;
+
+            if !order.is_triggered(current_price) {
+                return Err(MarketError::OrderNotTriggered);
+            }
+
+            if order.execution_fee > 0 {
+                let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+                vault
+                    .withdraw(user, id, order.execution_fee, caller)
+                    .map_err(|err| MarketError::VaultError(err))?;
+            }
+
+            self.create_position(
+                user,
+                id,
+                order.collateral_asset,
+                order.collateral_amount,
+                order.is_long,
+                order.leverage,
+                None,
+                None,
+            )?;
+
+            self.emit_event(OrderExecuted {
+                user,
+                id,
+                keeper: caller,
+                price: current_price,
+            });
+
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        pub fn increase_position_native(&mut self, id: u128, leverage: u32) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
+            let caller = self.env().caller();
+            self.ensure_native_collateral(caller, id)?;
+
+            let collateral_amount = self.env().transferred_value();
+            self.wrap_native(collateral_amount)?;
+
+            self.merge_into_position(caller, id, collateral_amount, leverage)?;
+
+            Ok(())
+        }
+
+        /// Adds `collateral_amount` at `leverage` to an existing position, averaging
+        /// the entry price by size.
+        #[ink(message)]
+        pub fn increase_position(
+            &mut self,
+            id: u128,
+            collateral_amount: Balance,
+            leverage: u32,
+        ) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Open)?;
+
             let caller = self.env().caller();
             let contract = self.env().account_id();
 
             let position = self
-                .positions
-                .get((caller, id))
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            let mut collateral: contract_ref!(PSP22) = position.collateral_asset.into();
+            collateral
+                .transfer_from(caller, contract, collateral_amount, Vec::new())
+                .map_err(|_| MarketError::TransferFailed)?;
+
+            self.merge_into_position(caller, id, collateral_amount, leverage)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn close(&mut self, id: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Close)?;
+
+            let caller = self.env().caller();
+
+            let position = self
+                .get_position(caller, id)
                 .ok_or(MarketError::PositionNotFound)?;
 
             let mut ids_for_user = self.ids_per_user.get(caller).unwrap_or_default();
@@ -533,84 +2871,187 @@ pub mod market {
                 return Err(MarketError::PositionNotFound);
             }
 
-            let (underlying_asset_symbol, underlying_asset_decimals) =
-                self.get_symbol_and_decimals(self.underlying_asset)?;
-            let underlying_price = self.get_price(underlying_asset_symbol)?;
+            self.accrue_funding()?;
+            self.settle_position(&position, position.collateral_amount, None)?;
+
+            let index_to_remove = ids_for_user.iter().position(|&x| x == id).unwrap();
+            ids_for_user.swap_remove(index_to_remove);
+            self.ids_per_user.insert(caller, &ids_for_user);
 
-            let (collateral_asset_symbol, collateral_asset_decimals) =
-                self.get_symbol_and_decimals(position.collateral_asset)?;
-            let collateral_price = self.get_price(collateral_asset_symbol)?;
+            self.positions.remove((caller, id));
 
-            let pnl_percent = self.calculate_pnl_percent(
-                position.entry_price,
-                underlying_price,
-                position.leverage,
-                position.is_long,
-            )?;
+            Ok(())
+        }
+
+        /// Closes `close_amount` of the position's collateral and keeps the rest open
+        /// at the same entry and liquidation price. The rest has to stay above the minimum
+        /// collateral, close the whole position otherwise.
+        #[ink(message)]
+        pub fn close_partial(&mut self, id: u128, close_amount: Balance) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Close)?;
+
+            let caller = self.env().caller();
+
+            let mut position = self
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            if close_amount == 0 || close_amount >= position.collateral_amount {
+                return Err(MarketError::InvalidAmount);
+            }
+
+            self.accrue_funding()?;
+            self.settle_position(&position, close_amount, None)?;
+            self.reduce_position(&mut position, close_amount)?;
+
+            self.insert_position(&position);
+            self.emit_position_updated(&position);
+
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        pub fn add_collateral_native(&mut self, id: u128) -> Result<(), MarketError> {
+            let caller = self.env().caller();
+            self.ensure_native_collateral(caller, id)?;
+
+            let collateral_amount = self.env().transferred_value();
+            self.wrap_native(collateral_amount)?;
+
+            self.increase_collateral(caller, id, collateral_amount)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_collateral(&mut self, id: u128, collateral_amount: Balance) -> Result<(), MarketError> {
+            let caller = self.env().caller();
+            let contract = self.env().account_id();
+
+            let position = self
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            let mut collateral: contract_ref!(PSP22) = position.collateral_asset.into();
+            collateral
+                .transfer_from(caller, contract, collateral_amount, Vec::new())
+                .map_err(|_| MarketError::TransferFailed)?;
+
+            self.increase_collateral(caller, id, collateral_amount)?;
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_collateral(&mut self, id: u128, collateral_amount: Balance) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Close)?;
+
+            let caller = self.env().caller();
+
+            let mut position = self
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            if collateral_amount == 0 || collateral_amount >= position.collateral_amount {
+                return Err(MarketError::InvalidAmount);
+            }
+
+            self.accrue_funding()?;
+
+            let removed_collateral_usd = position.collateral_usd
+                .checked_mul(collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("remove_collateral_1")))?
+                .checked_div(position.collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("remove_collateral_2")))?;
+
+            let collateral_usd = position.collateral_usd
+                .checked_sub(removed_collateral_usd)
+                .ok_or(MarketError::Overflow(String::from("remove_collateral_3")))?;
+
+            position.collateral_amount = position.collateral_amount
+                .checked_sub(collateral_amount)
+                .ok_or(MarketError::Overflow(String::from("remove_collateral_4")))?;
+
+            self.update_margin(&mut position, collateral_usd)?;
+            self.validate_position_size(position.collateral_usd, position.leverage)?;
 
             let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            vault
+                .withdraw(caller, id, collateral_amount, caller)
+                .map_err(|err| MarketError::VaultError(err))?;
 
-            if pnl_percent > 0 {
-                vault
-                    .withdraw(caller, id, position.collateral_amount, caller)
-                    .map_err(|err| MarketError::VaultError(err))?;
+            self.insert_position(&position);
+            self.emit_position_updated(&position);
 
-                let pnl_usd = pnl_percent
-                    .checked_mul(position.collateral_usd as i128)
-                    .ok_or(MarketError::Overflow(String::from("close_1")))?
-                    .checked_div(100)
-                    .ok_or(MarketError::Overflow(String::from("close_2")))?;
+            Ok(())
+        }
 
-                let payout_amount = self.calculate_asset_amount_from_usd(
-                    pnl_usd as u128,
-                    underlying_price,
-                    underlying_asset_decimals,
-                )?;
+        /// Sets or clears the take-profit and stop-loss prices of the caller's position. Levels
+        /// that the current price has already reached are rejected.
+        #[ink(message)]
+        pub fn set_tp_sl(
+            &mut self,
+            id: u128,
+            take_profit: Option<u128>,
+            stop_loss: Option<u128>,
+        ) -> Result<(), MarketError> {
+            let caller = self.env().caller();
+
+            let mut position = self
+                .get_position(caller, id)
+                .ok_or(MarketError::PositionNotFound)?;
+
+            position.take_profit = take_profit;
+            position.stop_loss = stop_loss;
+
+            if take_profit.is_some() || stop_loss.is_some() {
+                let current_price = self.fetch_price(self.underlying_asset)?;
+
+                if !position.has_valid_triggers(current_price) {
+                    return Err(MarketError::InvalidTriggerPrice);
+                }
+            }
+
+            self.insert_position(&position);
+            self.emit_position_updated(&position);
+
+            Ok(())
+        }
+
+        /// Closes a position whose take-profit or stop-loss price has been reached, or whose
+        /// profit has hit the max profit multiple, paying the caller `trigger_fee` basis points of
+        /// the collateral returned to the user.
+        #[ink(message)]
+        pub fn execute_trigger(&mut self, user: AccountId, id: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Close)?;
+
+            let caller = self.env().caller();
 
-                let mut asset: contract_ref!(PSP22) = self.underlying_asset.into();
-                asset
-                    .transfer(caller, payout_amount, Vec::new())
-                    .map_err(|_| MarketError::TransferFailed)?;
-            } else if pnl_percent < 0 {
-                let pnl_usd = pnl_percent
-                    .checked_mul(position.collateral_usd as i128)
-                    .ok_or(MarketError::Overflow(String::from("close_3")))?
-                    .checked_div(100)
-                    .ok_or(MarketError::Overflow(String::from("close_4")))?;
-
-                let rest_collateral_usd: i128 = (position
-                    .collateral_usd as i128)
-                    .checked_add(pnl_usd)
-                    .ok_or(MarketError::Overflow(String::from("close_5")))?;
-
-                let rest_collateral_amount = self.calculate_asset_amount_from_usd(
-                    rest_collateral_usd as u128,
-                    collateral_price,
-                    collateral_asset_decimals,
-                )?;
+            let position = self
+                .get_position(user, id)
+                .ok_or(MarketError::PositionNotFound)?;
 
-                vault
-                    .withdraw(caller, id, rest_collateral_amount, caller)
-                    .map_err(|err| MarketError::VaultError(err))?;
+            let mut ids_for_user = self.ids_per_user.get(user).unwrap_or_default();
+            if !ids_for_user.contains(&id) {
+                return Err(MarketError::PositionNotFound);
+            }
 
-                let collateral_for_contract: u128 = position.collateral_amount
-                    .checked_sub(rest_collateral_amount)
-                    .ok_or(MarketError::Overflow(String::from("close_6")))?;
+            let current_price = self.fetch_price(self.underlying_asset)?;
 
-                vault
-                    .withdraw(caller, id, collateral_for_contract, contract)
-                    .map_err(|err| MarketError::VaultError(err))?;
-            } else {
-                vault
-                    .withdraw(caller, id, position.collateral_amount, caller)
-                    .map_err(|err| MarketError::VaultError(err))?;
+            if !position.is_triggered(current_price)
+                && !self.is_profit_capped(&position, current_price)?
+            {
+                return Err(MarketError::TriggerNotReached);
             }
 
+            self.accrue_funding()?;
+            self.settle_position(&position, position.collateral_amount, Some(caller))?;
+
             let index_to_remove = ids_for_user.iter().position(|&x| x == id).unwrap();
             ids_for_user.swap_remove(index_to_remove);
-            self.ids_per_user.insert(caller, &ids_for_user);
+            self.ids_per_user.insert(user, &ids_for_user);
 
-            self.positions.remove((caller, id));
+            self.positions.remove((user, id));
 
             Ok(())
         }
@@ -618,25 +3059,18 @@ pub mod market {
         #[ink(message)]
         pub fn is_liquidatable(&mut self, user: AccountId, id: u128) -> Result<bool, MarketError> {
             let position = self
-                .positions
-                .get((user, id))
+                .get_position(user, id)
                 .ok_or(MarketError::PositionNotFound)?;
 
-            let (symbol, _decimals) = self.get_symbol_and_decimals(self.underlying_asset)?;
-            let current_price = self.get_price(symbol)?;
+            let current_price = self.get_price(self.underlying_asset)?;
 
-            let pnl_percent = self.calculate_pnl_percent(
-                position.entry_price,
-                current_price,
-                position.leverage,
-                position.is_long,
-            )?;
-
-            Ok(pnl_percent <= self.liquidation_threshold as i128)
+            self.is_below_liquidation_threshold(&position, current_price)
         }
 
         #[ink(message)]
         pub fn liquidate(&mut self, user: AccountId, id: u128) -> Result<(), MarketError> {
+            self.ensure_not_paused(PauseScope::Liquidation)?;
+
             let caller = self.env().caller();
             let contract = self.env().account_id();
 
@@ -645,90 +3079,344 @@ pub mod market {
                 return Err(MarketError::PositionNotFound);
             }
 
+            self.accrue_funding()?;
+
             if !self.is_liquidatable(user, id)? {
                 return Err(MarketError::NotLiquidatable);
             }
 
             let position = self
-                .positions
-                .get((user, id))
+                .get_position(user, id)
                 .ok_or(MarketError::PositionNotFound)?;
 
-            let (symbol, _decimals) = self.get_symbol_and_decimals(self.underlying_asset)?;
-            let current_price = self.get_price(symbol)?;
+            let current_price = self.fetch_price(self.underlying_asset)?;
 
-            let pnl_percent = self.calculate_pnl_percent(
-                position.entry_price,
-                current_price,
-                position.leverage,
-                position.is_long,
-            )?;
-
-            let leftover_collateral = pnl_percent
-                .checked_mul(position.collateral_usd as i128)
+            let leftover_usd = self
+                .calculate_net_pnl_usd(&position, current_price)?
+                .checked_add(position.collateral_usd as i128)
                 .ok_or(MarketError::Overflow(String::from("liquidate_1")))?
-                .checked_div(100)
+                .clamp(0, position.collateral_usd as i128) as u128;
+
+            let leftover_collateral = position.collateral_amount
+                .checked_mul(leftover_usd)
                 .ok_or(MarketError::Overflow(String::from("liquidate_2")))?
-                .checked_add(position.collateral_usd as i128)
+                .checked_div(position.collateral_usd)
                 .ok_or(MarketError::Overflow(String::from("liquidate_3")))?;
 
             let seize_amount = leftover_collateral
-                .checked_mul(self.liquidation_penalty as i128)
+                .checked_mul(self.liquidation_penalty as u128)
                 .ok_or(MarketError::Overflow(String::from("liquidate_4")))?
-                .checked_div(100)
+                .checked_div(BASIS_POINTS)
                 .ok_or(MarketError::Overflow(String::from("liquidate_5")))?;
 
             let owner_collateral = leftover_collateral
                 .checked_sub(seize_amount)
                 .ok_or(MarketError::Overflow(String::from("liquidate_6")))?;
 
-            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
-            vault
-                .withdraw(user, id, owner_collateral as u128, user)
-                .map_err(|err| MarketError::VaultError(err))?;
-
             let deployer_collateral = seize_amount
-                .checked_mul(self.protocol_fee as i128)
+                .checked_mul(self.protocol_fee as u128)
                 .ok_or(MarketError::Overflow(String::from("liquidate_7")))?
-                .checked_div(100)
+                .checked_div(BASIS_POINTS)
                 .ok_or(MarketError::Overflow(String::from("liquidate_8")))?;
 
-            vault
-                .withdraw(user, id, deployer_collateral as u128, self.owner)
-                .map_err(|err| MarketError::VaultError(err))?;
-
             let caller_collateral = seize_amount
-                .checked_mul(
-                    (100i128)
-                        .checked_sub(self.protocol_fee as i128)
-                        .ok_or(MarketError::Overflow(String::from("liquidate_9")))?
-                )
+                .checked_sub(deployer_collateral)
+                .ok_or(MarketError::Overflow(String::from("liquidate_9")))?;
+
+            // the accrued borrow fee is part of the collateral lost, capped at what was lost
+            let borrow_fee_usd = self
+                .calculate_borrow_fee_usd(&position)?
+                .min(position.collateral_usd.saturating_sub(leftover_usd));
+            let borrow_fee_amount = position.collateral_amount
+                .checked_mul(borrow_fee_usd)
                 .ok_or(MarketError::Overflow(String::from("liquidate_10")))?
-                .checked_div(100)
+                .checked_div(position.collateral_usd)
                 .ok_or(MarketError::Overflow(String::from("liquidate_11")))?;
 
-            vault
-                .withdraw(user, id, caller_collateral as u128, caller)
-                .map_err(|err| MarketError::VaultError(err))?;
+            // collateral lost to the price move stays with LPs, the protocol fee is
+            // held until claimed
+            let collateral_for_contract = position.collateral_amount
+                .checked_sub(leftover_collateral)
+                .ok_or(MarketError::Overflow(String::from("liquidate_12")))?
+                .checked_add(deployer_collateral)
+                .ok_or(MarketError::Overflow(String::from("liquidate_13")))?;
 
-            let collateral_for_contract: u128 = position.collateral_amount
-                .checked_sub(leftover_collateral as u128)
-                .ok_or(MarketError::Overflow(String::from("close_6")))?;
+            let mut vault: contract_ref!(CollateralVault) = self.vault.into();
+            if owner_collateral > 0 {
+                vault
+                    .withdraw(user, id, owner_collateral, user)
+                    .map_err(|err| MarketError::VaultError(err))?;
+            }
 
-            vault
-                .withdraw(caller, id, collateral_for_contract, contract)
-                .map_err(|err| MarketError::VaultError(err))?;
+            if caller_collateral > 0 {
+                vault
+                    .withdraw(user, id, caller_collateral, caller)
+                    .map_err(|err| MarketError::VaultError(err))?;
+            }
+
+            if collateral_for_contract > 0 {
+                vault
+                    .withdraw(user, id, collateral_for_contract, contract)
+                    .map_err(|err| MarketError::VaultError(err))?;
+            }
+
+            let protocol_fees = self.protocol_fees
+                .get(position.collateral_asset)
+                .unwrap_or_default()
+                .checked_add(deployer_collateral)
+                .ok_or(MarketError::Overflow(String::from("liquidate_14")))?;
+            self.protocol_fees.insert(position.collateral_asset, &protocol_fees);
+            self.accrue_protocol_fee(position.collateral_asset, borrow_fee_amount)?;
+
+            let notional_usd = self.calculate_notional_usd(position.collateral_usd, position.leverage)?;
+            self.decrease_open_interest(
+                notional_usd,
+                position.entry_price,
+                position.is_long,
+                position.reserved_amount,
+            )?;
 
             let index_to_remove = ids_for_user.iter().position(|&x| x == id).unwrap();
             ids_for_user.swap_remove(index_to_remove);
-            self.ids_per_user.insert(caller, &ids_for_user);
+            self.ids_per_user.insert(user, &ids_for_user);
 
             self.positions.remove((user, id));
 
+            self.emit_event(PositionLiquidated {
+                user,
+                id,
+                liquidator: caller,
+                penalty: seize_amount,
+                protocol_fee: deployer_collateral,
+            });
+
             Ok(())
         }
     }
 
+    #[ink(event)]
+    pub struct Approval {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct Transfer {
+        #[ink(topic)]
+        from: Option<AccountId>,
+        #[ink(topic)]
+        to: Option<AccountId>,
+        value: u128,
+    }
+
+    #[ink(event)]
+    pub struct LiquidityAdded {
+        #[ink(topic)]
+        provider: AccountId,
+        amount: u128,
+        shares: u128,
+    }
+
+    #[ink(event)]
+    pub struct LiquidityRemoved {
+        #[ink(topic)]
+        provider: AccountId,
+        amount: u128,
+        shares: u128,
+    }
+
+    #[ink(event)]
+    pub struct Upgraded {
+        code_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct Migrated {
+        from_version: u32,
+        to_version: u32,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferStarted {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleGranted {
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct PauseUpdated {
+        scope: PauseScope,
+        paused: bool,
+    }
+
+    #[ink(event)]
+    pub struct MarketDeprecated {}
+
+    #[ink(event)]
+    pub struct ConfigChangeQueued {
+        #[ink(topic)]
+        id: u128,
+        change: ConfigChange,
+        eta: u32,
+    }
+
+    #[ink(event)]
+    pub struct ConfigChangeExecuted {
+        #[ink(topic)]
+        id: u128,
+        change: ConfigChange,
+    }
+
+    #[ink(event)]
+    pub struct ConfigChangeCancelled {
+        #[ink(topic)]
+        id: u128,
+    }
+
+    #[ink(event)]
+    pub struct ProtocolFeesClaimed {
+        #[ink(topic)]
+        asset: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        amount: u128,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawRequested {
+        #[ink(topic)]
+        provider: AccountId,
+        id: u128,
+        shares: u128,
+        block_claimable: u32,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawCancelled {
+        #[ink(topic)]
+        provider: AccountId,
+        id: u128,
+    }
+
+    #[ink(event)]
+    pub struct PositionOpened {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+        collateral_asset: AccountId,
+        collateral_amount: u128,
+        collateral_usd: u128,
+        entry_price: u128,
+        leverage: u32,
+        is_long: bool,
+    }
+
+    #[ink(event)]
+    pub struct PositionUpdated {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+        collateral_amount: u128,
+        collateral_usd: u128,
+        entry_price: u128,
+        leverage: u32,
+        liquidation_price: u128,
+        take_profit: Option<u128>,
+        stop_loss: Option<u128>,
+    }
+
+    #[ink(event)]
+    pub struct PositionClosed {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+        // collateral closed out of the position
+        collateral_amount: u128,
+        // realised PnL in USD, net of funding
+        pnl: i128,
+        // set when a take-profit or stop-loss was executed by a keeper
+        keeper: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct PositionLiquidated {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+        #[ink(topic)]
+        liquidator: AccountId,
+        penalty: u128,
+        protocol_fee: u128,
+    }
+
+    #[ink(event)]
+    pub struct OrderCreated {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+        collateral_asset: AccountId,
+        collateral_amount: u128,
+        execution_fee: u128,
+        trigger_price: u128,
+        leverage: u32,
+        is_long: bool,
+        block_expiry: u32,
+    }
+
+    #[ink(event)]
+    pub struct OrderCancelled {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+    }
+
+    #[ink(event)]
+    pub struct OrderExecuted {
+        #[ink(topic)]
+        user: AccountId,
+        #[ink(topic)]
+        id: u128,
+        #[ink(topic)]
+        keeper: AccountId,
+        price: u128,
+    }
+
     impl PSP22Metadata for Market {
         #[ink(message)]
         fn token_name(&self) -> Option<String> {
@@ -744,6 +3432,71 @@ pub mod market {
         }
     }
 
+    impl AccessControl for Market {
+        #[ink(message)]
+        fn owner(&self) -> AccountId {
+            self.access_control.owner()
+        }
+
+        #[ink(message)]
+        fn pending_owner(&self) -> Option<AccountId> {
+            self.access_control.pending_owner()
+        }
+
+        #[ink(message)]
+        fn has_role(&self, role: Role, account: AccountId) -> bool {
+            self.access_control.has_role(role, account)
+        }
+
+        #[ink(message)]
+        fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .transfer_ownership(self.env().caller(), new_owner)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn accept_ownership(&mut self) -> Result<(), AccessControlError> {
+            let events = self.access_control.accept_ownership(self.env().caller())?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn renounce_ownership(&mut self) -> Result<(), AccessControlError> {
+            let events = self.access_control.renounce_ownership(self.env().caller())?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn grant_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .grant_role(self.env().caller(), role, account)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .revoke_role(self.env().caller(), role, account)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn renounce_role(&mut self, role: Role) -> Result<(), AccessControlError> {
+            let events = self.access_control.renounce_role(self.env().caller(), role);
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+    }
+
     impl PSP22 for Market {
         #[ink(message)]
         fn total_supply(&self) -> u128 {
@@ -767,7 +3520,8 @@ pub mod market {
             value: u128,
             _data: Vec<u8>,
         ) -> Result<(), PSP22Error> {
-            let _events = self.data.transfer(self.env().caller(), to, value)?;
+            let events = self.data.transfer(self.env().caller(), to, value)?;
+            self.emit_events(events);
             Ok(())
         }
 
@@ -779,15 +3533,17 @@ pub mod market {
             value: u128,
             _data: Vec<u8>,
         ) -> Result<(), PSP22Error> {
-            let _events = self
+            let events = self
                 .data
                 .transfer_from(self.env().caller(), from, to, value)?;
+            self.emit_events(events);
             Ok(())
         }
 
         #[ink(message)]
         fn approve(&mut self, spender: AccountId, value: u128) -> Result<(), PSP22Error> {
-            let _events = self.data.approve(self.env().caller(), spender, value)?;
+            let events = self.data.approve(self.env().caller(), spender, value)?;
+            self.emit_events(events);
             Ok(())
         }
 
@@ -797,9 +3553,10 @@ pub mod market {
             spender: AccountId,
             delta_value: u128,
         ) -> Result<(), PSP22Error> {
-            let _events =
-                self.data
-                    .increase_allowance(self.env().caller(), spender, delta_value)?;
+            let events = self
+                .data
+                .increase_allowance(self.env().caller(), spender, delta_value)?;
+            self.emit_events(events);
             Ok(())
         }
 
@@ -809,10 +3566,512 @@ pub mod market {
             spender: AccountId,
             delta_value: u128,
         ) -> Result<(), PSP22Error> {
-            let _events =
-                self.data
-                    .decrease_allowance(self.env().caller(), spender, delta_value)?;
+            let events = self
+                .data
+                .decrease_allowance(self.env().caller(), spender, delta_value)?;
+            self.emit_events(events);
             Ok(())
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::{test::*, DefaultEnvironment as E};
+
+        // 1_000 USD of collateral at 5x on an entry price of 2_000 USD, 2.5 units of size
+        fn position(is_long: bool) -> Position {
+            Position::new(
+                AccountId::from([2; 32]),
+                0,
+                1_000_000,
+                AccountId::from([1; 32]),
+                1_000_000_000,
+                2_000_000_000,
+                50_000,
+                is_long,
+                0,
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+            )
+        }
+
+        fn order(is_long: bool, trigger_price: u128) -> Order {
+            Order::new(
+                AccountId::from([2; 32]),
+                0,
+                1_000_000,
+                AccountId::from([1; 32]),
+                0,
+                trigger_price,
+                50_000,
+                is_long,
+                0,
+                100,
+            )
+        }
+
+        #[ink::test]
+        fn migrate_is_owner_only() {
+            let acc = default_accounts::<E>();
+            set_caller::<E>(acc.alice);
+            let mut market = Market::default();
+
+            set_caller::<E>(acc.bob);
+            assert!(market.migrate().is_err());
+
+            set_caller::<E>(acc.alice);
+            assert!(market.migrate().is_ok());
+            assert_eq!(market.view_storage_version(), STORAGE_VERSION);
+        }
+
+        #[ink::test]
+        fn reserve_is_capped_at_max_profit() {
+            let market = Market::default();
+
+            // 5x on 1_000 USD, max profit 9x collateral exceeds the notional
+            assert_eq!(
+                market.calculate_reserve_usd(5_000_000_000, 1_000_000_000),
+                Ok(5_000_000_000)
+            );
+            // 50x on 1_000 USD, only the 9_000 USD max profit is reserved
+            assert_eq!(
+                market.calculate_reserve_usd(50_000_000_000, 1_000_000_000),
+                Ok(9_000_000_000)
+            );
+        }
+
+        #[ink::test]
+        fn stored_reserve_is_released_after_the_profit_multiple_changes() {
+            let mut market = Market::default();
+            let mut long = position(true);
+            long.reserved_amount = 4_500;
+            market.reserved_amount = 4_500;
+            market.long_open_interest = 5_000_000_000;
+
+            market.max_profit_multiple = 2;
+
+            assert_eq!(market.calculate_released_reserve(&long, 500_000), Ok(2_250));
+            let released = market.calculate_released_reserve(&long, long.collateral_amount).unwrap();
+            market.decrease_open_interest(5_000_000_000, long.entry_price, true, released).unwrap();
+            assert_eq!(market.reserved_amount, 0);
+            assert_eq!(market.long_open_interest, 0);
+        }
+
+        #[ink::test]
+        fn partial_close_keeps_a_proportional_rest() {
+            let mut market = Market::default();
+            let mut long = position(true);
+            long.reserved_amount = 4_500;
+
+            market.reduce_position(&mut long, 250_000).unwrap();
+            assert_eq!(long.collateral_amount, 750_000);
+            assert_eq!(long.collateral_usd, 750_000_000);
+            assert_eq!(long.reserved_amount, 3_375);
+            assert_eq!(long.leverage, 50_000);
+
+            // the rest has to stay above the minimum collateral
+            market.min_collateral_usd = 600_000_000;
+            assert_eq!(
+                market.reduce_position(&mut long.clone(), 250_000),
+                Err(MarketError::CollateralTooLow)
+            );
+            assert!(market.reduce_position(&mut long, 150_000).is_ok());
+            assert_eq!(long.collateral_usd, 600_000_000);
+        }
+
+        #[ink::test]
+        fn partial_closes_release_the_whole_reserve() {
+            let market = Market::default();
+            let mut long = position(true);
+            long.reserved_amount = 4_500;
+
+            // rounding down leaves the remainder with the position, the last close releases it
+            let released = market.calculate_released_reserve(&long, 333_333).unwrap();
+            assert_eq!(released, 1_499);
+            market.reduce_position(&mut long, 333_333).unwrap();
+            assert_eq!(long.reserved_amount, 3_001);
+
+            let released_on_close = market
+                .calculate_released_reserve(&long, long.collateral_amount)
+                .unwrap();
+            assert_eq!(released + released_on_close, 4_500);
+        }
+
+        #[ink::test]
+        fn merge_weights_entry_price_and_carries_fees_over() {
+            let mut market = Market::default();
+            market.long_open_interest = 3_000_000_000;
+            market.short_open_interest = 1_000_000_000;
+            market.funding_index = 140_000;
+            market.borrow_index = 3_600;
+
+            let long = position(true);
+            assert_eq!(market.calculate_funding_usd(&long), Ok(700));
+            assert_eq!(market.calculate_borrow_fee_usd(&long), Ok(500_000));
+
+            // 5_000 USD at 2_000 merged with 5_000 USD at 2_500, both 5x
+            let (entry_price, entry_funding_index, entry_borrow_index) = market
+                .calculate_merged_entry(&long, 5_000_000_000, 1_000_000_000, 2_500_000_000)
+                .unwrap();
+            assert_eq!(entry_price, 2_222_222_222);
+            assert_eq!(entry_funding_index, 70_000);
+            assert_eq!(entry_borrow_index, 1_800);
+
+            let mut merged = long.clone();
+            merged.collateral_usd = 2_000_000_000;
+            merged.entry_price = entry_price;
+            merged.entry_funding_index = entry_funding_index;
+            merged.entry_borrow_index = entry_borrow_index;
+
+            // what the original position owed is still owed after the merge
+            assert_eq!(market.calculate_funding_usd(&merged), Ok(700));
+            assert_eq!(market.calculate_borrow_fee_usd(&merged), Ok(500_000));
+        }
+
+        #[ink::test]
+        fn fees_come_from_profit_before_collateral() {
+            let market = Market::default();
+            let close_usd = 1_000_000_000;
+            let fees_usd = 10_000_000;
+
+            // profit covers the fees
+            assert_eq!(
+                market.split_fees(50_000_000, fees_usd, close_usd),
+                Ok((10_000_000, 0, 1_000_000_000))
+            );
+
+            // profit covers part of the fees, the collateral the rest
+            assert_eq!(
+                market.split_fees(4_000_000, fees_usd, close_usd),
+                Ok((4_000_000, 6_000_000, 994_000_000))
+            );
+
+            // on a loss all fees come from the collateral
+            assert_eq!(
+                market.split_fees(-100_000_000, fees_usd, close_usd),
+                Ok((0, 10_000_000, 890_000_000))
+            );
+
+            // and never more than the collateral
+            assert_eq!(
+                market.split_fees(-100_000_000, fees_usd, 5_000_000),
+                Ok((0, 5_000_000, 0))
+            );
+        }
+
+        #[ink::test]
+        fn borrow_fee_is_charged_on_the_reserve() {
+            let mut market = Market::default();
+            // one basis point for one hour
+            market.borrow_index = 3_600;
+
+            // 5x, the whole 5_000 USD notional is reserved
+            assert_eq!(market.calculate_borrow_fee_usd(&position(true)), Ok(500_000));
+
+            // 50x, only the 9_000 USD max profit is reserved
+            let mut long = position(true);
+            long.leverage = 500_000;
+            assert_eq!(market.calculate_borrow_fee_usd(&long), Ok(900_000));
+        }
+
+        #[ink::test]
+        fn deprecated_market_rejects_opens_and_deposits_even_when_unpaused() {
+            let acc = default_accounts::<E>();
+            set_caller::<E>(acc.alice);
+            let mut market = Market::default();
+
+            set_caller::<E>(acc.bob);
+            assert!(market.deprecate().is_err());
+
+            set_caller::<E>(acc.alice);
+            assert!(market.deprecate().is_ok());
+            assert!(market.is_deprecated());
+
+            market.set_paused(PauseScope::Open, false).unwrap();
+            assert_eq!(market.deposit(1), Err(MarketError::Deprecated));
+            assert_eq!(market.ensure_not_paused(PauseScope::Open), Err(MarketError::Deprecated));
+            assert_eq!(market.ensure_not_paused(PauseScope::Close), Ok(()));
+            assert_eq!(market.ensure_not_paused(PauseScope::Withdraw), Ok(()));
+        }
+
+        #[ink::test]
+        fn pnl_follows_the_price_move_times_size() {
+            let market = Market::default();
+
+            assert_eq!(market.calculate_pnl_usd(&position(true), 2_200_000_000), Ok(500_000_000));
+            assert_eq!(market.calculate_pnl_usd(&position(false), 2_200_000_000), Ok(-500_000_000));
+            assert_eq!(market.calculate_pnl_usd(&position(true), 1_800_000_000), Ok(-500_000_000));
+            assert_eq!(market.calculate_pnl_usd(&position(false), 1_800_000_000), Ok(500_000_000));
+            assert_eq!(market.calculate_pnl_usd(&position(true), 2_000_000_000), Ok(0));
+        }
+
+        #[ink::test]
+        fn pnl_keeps_sub_percent_moves() {
+            let market = Market::default();
+
+            // +0.05% on 5_000 USD notional
+            assert_eq!(market.calculate_pnl_usd(&position(true), 2_001_000_000), Ok(2_500_000));
+            assert_eq!(market.calculate_pnl_usd(&position(false), 2_001_000_000), Ok(-2_500_000));
+            // -0.0001%
+            assert_eq!(market.calculate_pnl_usd(&position(true), 1_999_998_000), Ok(-5_000));
+        }
+
+        #[ink::test]
+        fn liquidation_price_sits_at_the_threshold_move() {
+            let mut market = Market::default();
+            market.liquidation_threshold = -6_000;
+
+            // -60% of collateral at 5x is a 12% move
+            assert_eq!(market.calculate_liquidation_price(2_000_000_000, 50_000, true), Ok(1_760_000_000));
+            assert_eq!(market.calculate_liquidation_price(2_000_000_000, 50_000, false), Ok(2_240_000_000));
+            // 1.5x
+            assert_eq!(market.calculate_liquidation_price(2_000_000_000, 15_000, true), Ok(1_200_000_000));
+        }
+
+        #[ink::test]
+        fn liquidation_threshold_is_reached_at_the_liquidation_price() {
+            let mut market = Market::default();
+            market.liquidation_threshold = -6_000;
+
+            let long = position(true);
+            assert_eq!(market.is_below_liquidation_threshold(&long, 1_760_000_000), Ok(true));
+            assert_eq!(market.is_below_liquidation_threshold(&long, 1_760_000_001), Ok(false));
+            assert_eq!(market.is_below_liquidation_threshold(&long, 1_500_000_000), Ok(true));
+            assert_eq!(market.is_below_liquidation_threshold(&long, 2_500_000_000), Ok(false));
+
+            let short = position(false);
+            assert_eq!(market.is_below_liquidation_threshold(&short, 2_240_000_000), Ok(true));
+            assert_eq!(market.is_below_liquidation_threshold(&short, 2_239_999_999), Ok(false));
+            assert_eq!(market.is_below_liquidation_threshold(&short, 1_500_000_000), Ok(false));
+        }
+
+        #[ink::test]
+        fn position_triggers_on_take_profit_and_stop_loss() {
+            let mut long = position(true);
+            assert!(!long.is_triggered(2_100_000_000));
+
+            long.take_profit = Some(2_200_000_000);
+            long.stop_loss = Some(1_900_000_000);
+            assert!(!long.is_triggered(2_100_000_000));
+            assert!(long.is_triggered(2_200_000_000));
+            assert!(long.is_triggered(1_900_000_000));
+            assert!(!long.is_triggered(1_900_000_001));
+
+            let mut short = position(false);
+            short.take_profit = Some(1_800_000_000);
+            short.stop_loss = Some(2_100_000_000);
+            assert!(!short.is_triggered(2_000_000_000));
+            assert!(short.is_triggered(1_800_000_000));
+            assert!(short.is_triggered(2_100_000_000));
+            assert!(!short.is_triggered(1_800_000_001));
+        }
+
+        fn advance_blocks(blocks: u32) {
+            for _ in 0..blocks {
+                advance_block::<E>();
+            }
+        }
+
+        #[ink::test]
+        fn funding_is_zero_without_open_interest() {
+            let mut market = Market::default();
+
+            assert_eq!(market.calculate_funding_rate(), Ok(0));
+            advance_blocks(10);
+            assert_eq!(market.calculate_funding_index(), Ok(0));
+            assert_eq!(market.calculate_funding_usd(&position(true)), Ok(0));
+
+            market.accrue_funding().unwrap();
+            assert_eq!(market.funding_index, 0);
+        }
+
+        #[ink::test]
+        fn longs_pay_funding_when_skewed_long() {
+            let mut market = Market::default();
+            market.long_open_interest = 3_000_000_000;
+            market.short_open_interest = 1_000_000_000;
+
+            // half of the open interest is skew
+            assert_eq!(market.calculate_funding_rate(), Ok(14_000));
+            advance_blocks(10);
+            assert_eq!(market.calculate_funding_index(), Ok(140_000));
+            assert_eq!(market.calculate_funding_usd(&position(true)), Ok(700));
+            assert_eq!(market.calculate_funding_usd(&position(false)), Ok(-700));
+
+            market.long_open_interest = 1_000_000_000;
+            market.short_open_interest = 3_000_000_000;
+            assert_eq!(market.calculate_funding_rate(), Ok(-14_000));
+        }
+
+        #[ink::test]
+        fn funding_carries_over_across_accruals() {
+            let mut market = Market::default();
+            market.long_open_interest = 3_000_000_000;
+            market.short_open_interest = 1_000_000_000;
+
+            advance_blocks(10);
+            market.accrue_funding().unwrap();
+            assert_eq!(market.funding_index, 140_000);
+            assert_eq!(market.last_funding_block, 10);
+
+            // the skew flips, the accrued index is kept and funding flows back
+            market.long_open_interest = 1_000_000_000;
+            market.short_open_interest = 3_000_000_000;
+            advance_blocks(5);
+            assert_eq!(market.calculate_funding_index(), Ok(70_000));
+
+            market.accrue_funding().unwrap();
+            assert_eq!(market.funding_index, 70_000);
+
+            // a position opened after the first accrual only owes what accrued since
+            let mut long = position(true);
+            long.entry_funding_index = 140_000;
+            assert_eq!(market.calculate_funding_usd(&long), Ok(-350));
+        }
+
+        #[ink::test]
+        fn withdraw_block_without_epochs_is_the_delay() {
+            let mut market = Market::default();
+            market.withdraw_delay = 3_600;
+
+            assert_eq!(market.calculate_withdraw_block(100), Ok(3_700));
+
+            market.withdraw_delay = 0;
+            assert_eq!(market.calculate_withdraw_block(100), Ok(100));
+        }
+
+        #[ink::test]
+        fn withdraw_block_rounds_up_to_the_next_epoch() {
+            let mut market = Market::default();
+            market.withdraw_delay = 3_600;
+            market.withdraw_epoch_length = 1_000;
+
+            assert_eq!(market.calculate_withdraw_block(100), Ok(4_000));
+            assert_eq!(market.calculate_withdraw_block(401), Ok(5_000));
+            assert!(market.calculate_withdraw_block(u32::MAX).is_err());
+        }
+
+        #[ink::test]
+        fn withdraw_block_on_an_epoch_boundary_is_kept() {
+            let mut market = Market::default();
+            market.withdraw_delay = 3_600;
+            market.withdraw_epoch_length = 1_000;
+
+            assert_eq!(market.calculate_withdraw_block(400), Ok(4_000));
+
+            market.withdraw_delay = 0;
+            assert_eq!(market.calculate_withdraw_block(0), Ok(0));
+            assert_eq!(market.calculate_withdraw_block(2_000), Ok(2_000));
+        }
+
+        #[ink::test]
+        fn vault_change_waits_for_pending_orders() {
+            let acc = default_accounts::<E>();
+            set_caller::<E>(acc.alice);
+            let mut market = Market::default();
+            market.config_timelock = 0;
+            market.open_orders.set(&1);
+
+            let id = market.queue_config_change(ConfigChange::Vault(acc.django)).unwrap();
+            assert_eq!(market.execute_config_change(id), Err(MarketError::OrdersOpen));
+
+            market.open_orders.set(&0);
+            assert_eq!(market.execute_config_change(id), Ok(()));
+            assert_eq!(market.vault, acc.django);
+        }
+
+        #[ink::test]
+        fn risk_parameters_wait_for_the_timelock() {
+            let acc = default_accounts::<E>();
+            set_caller::<E>(acc.alice);
+            let mut market = Market::default();
+            market.config_timelock = 10;
+            market.max_utilisation = 8_000;
+
+            assert_eq!(
+                market.queue_config_change(ConfigChange::MaxUtilisation(10_001)),
+                Err(MarketError::InvalidAmount)
+            );
+            assert_eq!(
+                market.queue_config_change(ConfigChange::PositionLimits(20_000, 10_000, 0, 0)),
+                Err(MarketError::InvalidLeverage)
+            );
+
+            let id = market.queue_config_change(ConfigChange::MaxUtilisation(9_000)).unwrap();
+            advance_blocks(9);
+            assert_eq!(market.execute_config_change(id), Err(MarketError::ConfigChangeNotReady));
+            assert_eq!(market.max_utilisation, 8_000);
+
+            advance_blocks(1);
+            assert_eq!(market.execute_config_change(id), Ok(()));
+            assert_eq!(market.max_utilisation, 9_000);
+        }
+
+        #[ink::test]
+        fn liquidation_price_follows_threshold_changes() {
+            let acc = default_accounts::<E>();
+            set_caller::<E>(acc.alice);
+            let mut market = Market::default();
+            market.config_timelock = 0;
+            market.liquidation_threshold = -6_000;
+
+            market.insert_position(&position(true));
+            let user = position(true).user;
+            assert_eq!(market.view_position(user, 0).unwrap().liquidation_price, 1_760_000_000);
+
+            let id = market.queue_config_change(ConfigChange::LiquidationThreshold(-8_000)).unwrap();
+            market.execute_config_change(id).unwrap();
+            assert_eq!(market.view_position(user, 0).unwrap().liquidation_price, 1_680_000_000);
+        }
+
+        #[ink::test]
+        fn native_top_up_requires_wazero_collateral() {
+            let mut market = Market::default();
+            market.insert_position(&position(true));
+
+            set_caller::<E>(position(true).user);
+            assert_eq!(market.add_collateral_native(0), Err(MarketError::NotSupported));
+            assert_eq!(market.increase_position_native(0, 20_000), Err(MarketError::NotSupported));
+        }
+
+        #[ink::test]
+        fn triggers_must_be_on_the_right_side_of_the_price() {
+            let mut long = position(true);
+            long.take_profit = Some(2_100_000_000);
+            long.stop_loss = Some(1_900_000_000);
+            assert!(long.has_valid_triggers(2_000_000_000));
+            assert!(!long.has_valid_triggers(2_100_000_000));
+            assert!(!long.has_valid_triggers(1_900_000_000));
+
+            // a short profits as the price falls
+            let mut short = position(false);
+            short.take_profit = Some(1_900_000_000);
+            short.stop_loss = Some(2_100_000_000);
+            assert!(short.has_valid_triggers(2_000_000_000));
+            assert!(!short.has_valid_triggers(1_900_000_000));
+            assert!(!short.has_valid_triggers(2_100_000_000));
+
+            short.take_profit = None;
+            short.stop_loss = None;
+            assert!(short.has_valid_triggers(2_000_000_000));
+        }
+
+        #[ink::test]
+        fn order_fills_below_for_longs_and_above_for_shorts() {
+            let long = order(true, 1_900_000_000);
+            assert!(long.is_triggered(1_900_000_000));
+            assert!(long.is_triggered(1_899_999_999));
+            assert!(!long.is_triggered(1_900_000_001));
+
+            let short = order(false, 2_100_000_000);
+            assert!(short.is_triggered(2_100_000_000));
+            assert!(short.is_triggered(2_100_000_001));
+            assert!(!short.is_triggered(2_099_999_999));
+        }
+    }
 }
@@ -0,0 +1,58 @@
+use ink::primitives::AccountId;
+use scale::{Decode, Encode};
+
+#[derive(Decode, Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug)]
+pub struct Order {
+    pub user: AccountId,
+    pub id: u128,
+    pub collateral_amount: u128,
+    pub collateral_asset: AccountId,
+    pub execution_fee: u128,
+    pub trigger_price: u128,
+    pub leverage: u32,
+    pub is_long: bool,
+    pub block_created: u32,
+    pub block_expiry: u32,
+}
+
+impl Order {
+    pub fn new(
+        user: AccountId,
+        id: u128,
+        collateral_amount: u128,
+        collateral_asset: AccountId,
+        execution_fee: u128,
+        trigger_price: u128,
+        leverage: u32,
+        is_long: bool,
+        block_created: u32,
+        block_expiry: u32,
+    ) -> Order {
+        Order {
+            user,
+            id,
+            collateral_amount,
+            collateral_asset,
+            execution_fee,
+            trigger_price,
+            leverage,
+            is_long,
+            block_created,
+            block_expiry,
+        }
+    }
+
+    /// A long order fills at or below the trigger price, a short order at or above it.
+    pub fn is_triggered(&self, price: u128) -> bool {
+        if self.is_long {
+            price <= self.trigger_price
+        } else {
+            price >= self.trigger_price
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use scale::{Decode, Encode};
+
+#[derive(Decode, Encode, Clone, Copy)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PauseScope {
+    Open,
+    Close,
+    Liquidation,
+    Deposit,
+    Withdraw,
+}
@@ -6,7 +6,7 @@ use scale::{Decode, Encode};
     feature = "std",
     derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
 )]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Position {
     pub user: AccountId,
     pub id: u128,
@@ -14,10 +14,16 @@ pub struct Position {
     pub collateral_asset: AccountId,
     pub collateral_usd: u128,
     pub entry_price: u128,
-    pub leverage: u8,
+    pub leverage: u32,
     pub is_long: bool,
     pub block_open: u32,
     pub liquidation_price: u128,
+    pub entry_funding_index: i128,
+    pub entry_borrow_index: u128,
+    // underlying set aside for this position, released as it closes
+    pub reserved_amount: u128,
+    pub take_profit: Option<u128>,
+    pub stop_loss: Option<u128>,
 }
 
 impl Position {
@@ -28,10 +34,15 @@ impl Position {
         collateral_asset: AccountId,
         collateral_usd: u128,
         entry_price: u128,
-        leverage: u8,
+        leverage: u32,
         is_long: bool,
         block_open: u32,
         liquidation_price: u128,
+        entry_funding_index: i128,
+        entry_borrow_index: u128,
+        reserved_amount: u128,
+        take_profit: Option<u128>,
+        stop_loss: Option<u128>,
     ) -> Position {
         Position {
             user,
@@ -44,6 +55,73 @@ impl Position {
             is_long,
             block_open,
             liquidation_price,
+            entry_funding_index,
+            entry_borrow_index,
+            reserved_amount,
+            take_profit,
+            stop_loss,
         }
     }
+
+    /// Whether `price` has reached the take-profit or stop-loss level of the position.
+    pub fn is_triggered(&self, price: u128) -> bool {
+        let take_profit_hit = match self.take_profit {
+            Some(take_profit) if self.is_long => price >= take_profit,
+            Some(take_profit) => price <= take_profit,
+            None => false,
+        };
+
+        let stop_loss_hit = match self.stop_loss {
+            Some(stop_loss) if self.is_long => price <= stop_loss,
+            Some(stop_loss) => price >= stop_loss,
+            None => false,
+        };
+
+        take_profit_hit || stop_loss_hit
+    }
+
+    /// Whether the take-profit and stop-loss levels are on the right side of `price`, so
+    /// neither fills at once: above it for a long's take-profit, below it for its stop-loss,
+    /// and the reverse for a short.
+    pub fn has_valid_triggers(&self, price: u128) -> bool {
+        let take_profit_valid = match self.take_profit {
+            Some(take_profit) if self.is_long => take_profit > price,
+            Some(take_profit) => take_profit < price,
+            None => true,
+        };
+
+        let stop_loss_valid = match self.stop_loss {
+            Some(stop_loss) if self.is_long => stop_loss < price,
+            Some(stop_loss) => stop_loss > price,
+            None => true,
+        };
+
+        take_profit_valid && stop_loss_valid
+    }
+}
+
+/// Positions are stored tagged with their layout, so a later layout can be added as a new
+/// variant and converted to the current one when read.
+#[derive(Decode, Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionedPosition {
+    V1(Position),
+}
+
+impl VersionedPosition {
+    pub fn into_latest(self) -> Position {
+        match self {
+            VersionedPosition::V1(position) => position,
+        }
+    }
+}
+
+impl From<Position> for VersionedPosition {
+    fn from(position: Position) -> Self {
+        VersionedPosition::V1(position)
+    }
 }
@@ -0,0 +1,38 @@
+use ink::primitives::AccountId;
+use scale::{Decode, Encode};
+
+#[derive(Decode, Encode)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+#[derive(Debug)]
+pub struct WithdrawRequest {
+    pub user: AccountId,
+    pub id: u128,
+    pub shares: u128,
+    pub block_requested: u32,
+    pub block_claimable: u32,
+}
+
+impl WithdrawRequest {
+    pub fn new(
+        user: AccountId,
+        id: u128,
+        shares: u128,
+        block_requested: u32,
+        block_claimable: u32,
+    ) -> WithdrawRequest {
+        WithdrawRequest {
+            user,
+            id,
+            shares,
+            block_requested,
+            block_claimable,
+        }
+    }
+
+    pub fn is_claimable(&self, block: u32) -> bool {
+        block >= self.block_claimable
+    }
+}
@@ -1,6 +1,9 @@
+use access_control::AccessControlError;
+
 #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
 #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
 pub enum VaultError {
+    AccessControlError(AccessControlError),
     AmountIsZero,
     AssetAlreadyExist,
     AssetNotFound,
@@ -9,6 +12,7 @@ pub enum VaultError {
     InsufficientBalance,
     MarketAlreadyExist,
     MarketNotFound,
-    NotAdmin,
+    Paused,
     TransferError,
+    UpgradeFailed,
 }
\ No newline at end of file
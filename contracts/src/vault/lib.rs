@@ -1,26 +1,35 @@
 #![cfg_attr(not(feature = "std"), no_std, no_main)]
 
 mod errors;
+mod pause;
 mod traits;
 
 pub use errors::VaultError;
+pub use pause::VaultPauseScope;
 pub use traits::CollateralVault;
 
 pub use self::vault::VaultRef;
 
 #[ink::contract]
 mod vault {
-    use crate::CollateralVault;
+    use crate::{CollateralVault, VaultPauseScope};
+    use access_control::{AccessControl, AccessControlData, AccessControlError, AccessControlEvent, Role};
+    use ink::codegen::EmitEvent;
     use ink::contract_ref;
     use ink::prelude::vec::Vec;
+    use ink::reflect::ContractEventBase;
     use ink::storage::Mapping;
     use psp22::PSP22;
 
     use crate::VaultError;
 
+    const STORAGE_VERSION: u32 = 1;
+
     #[ink(storage)]
     pub struct Vault {
-        admin: AccountId,
+        access_control: AccessControlData,
+        storage_version: u32,
+        paused_scopes: Vec<VaultPauseScope>,
         // (market, user, position) => (balance, collateral asset)
         balances: Mapping<(AccountId, AccountId, u128), (Balance, AccountId)>,
         markets: Vec<AccountId>,
@@ -30,15 +39,249 @@ mod vault {
     impl Vault {
         #[ink(constructor)]
         pub fn new() -> Self {
-            let caller = Self::env().caller();
-
             Self {
-                admin: caller,
+                access_control: AccessControlData::new(Self::env().caller()),
+                storage_version: STORAGE_VERSION,
+                paused_scopes: Default::default(),
                 balances: Default::default(),
                 markets: Default::default(),
                 assets: Default::default(),
             }
         }
+
+        #[ink(message)]
+        pub fn is_paused(&self, scope: VaultPauseScope) -> bool {
+            self.paused_scopes.contains(&scope)
+        }
+
+        #[ink(message)]
+        pub fn set_paused(&mut self, scope: VaultPauseScope, paused: bool) -> Result<(), VaultError> {
+            self.ensure_role(Role::Guardian)?;
+
+            let index = self.paused_scopes.iter().position(|&x| x == scope);
+            match (index, paused) {
+                (None, true) => self.paused_scopes.push(scope),
+                (Some(index), false) => {
+                    self.paused_scopes.swap_remove(index);
+                }
+                _ => return Ok(()),
+            }
+
+            self.emit_event(PauseUpdated { scope, paused });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: Hash) -> Result<(), VaultError> {
+            self.ensure_owner()?;
+
+            self.env()
+                .set_code_hash(&code_hash)
+                .map_err(|_| VaultError::UpgradeFailed)?;
+
+            self.emit_event(Upgraded { code_hash });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn view_storage_version(&self) -> u32 {
+            self.storage_version
+        }
+
+        #[ink(message)]
+        pub fn migrate(&mut self) -> Result<(), VaultError> {
+            self.ensure_owner()?;
+
+            let from_version = self.storage_version;
+            if from_version >= STORAGE_VERSION {
+                return Ok(());
+            }
+
+            self.storage_version = STORAGE_VERSION;
+
+            self.emit_event(Migrated {
+                from_version,
+                to_version: STORAGE_VERSION,
+            });
+
+            Ok(())
+        }
+
+        fn emit_event<E>(&self, event: E)
+        where
+            E: Into<<Vault as ContractEventBase>::Type>,
+        {
+            EmitEvent::<Vault>::emit_event(self.env(), event);
+        }
+
+        fn emit_access_control_events(&self, events: Vec<AccessControlEvent>) {
+            for event in events {
+                match event {
+                    AccessControlEvent::OwnershipTransferStarted {
+                        previous_owner,
+                        new_owner,
+                    } => self.emit_event(OwnershipTransferStarted {
+                        previous_owner,
+                        new_owner,
+                    }),
+                    AccessControlEvent::OwnershipTransferred {
+                        previous_owner,
+                        new_owner,
+                    } => self.emit_event(OwnershipTransferred {
+                        previous_owner,
+                        new_owner,
+                    }),
+                    AccessControlEvent::RoleGranted {
+                        role,
+                        account,
+                        sender,
+                    } => self.emit_event(RoleGranted {
+                        role,
+                        account,
+                        sender,
+                    }),
+                    AccessControlEvent::RoleRevoked {
+                        role,
+                        account,
+                        sender,
+                    } => self.emit_event(RoleRevoked {
+                        role,
+                        account,
+                        sender,
+                    }),
+                }
+            }
+        }
+
+        fn ensure_owner(&self) -> Result<(), VaultError> {
+            self.access_control
+                .ensure_owner(self.env().caller())
+                .map_err(VaultError::AccessControlError)
+        }
+
+        fn ensure_role(&self, role: Role) -> Result<(), VaultError> {
+            self.access_control
+                .ensure_role(self.env().caller(), role)
+                .map_err(VaultError::AccessControlError)
+        }
+    }
+
+    #[ink(event)]
+    pub struct PauseUpdated {
+        scope: VaultPauseScope,
+        paused: bool,
+    }
+
+    #[ink(event)]
+    pub struct Upgraded {
+        code_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct Migrated {
+        from_version: u32,
+        to_version: u32,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferStarted {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleGranted {
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoleRevoked {
+        role: Role,
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        sender: AccountId,
+    }
+
+    impl AccessControl for Vault {
+        #[ink(message)]
+        fn owner(&self) -> AccountId {
+            self.access_control.owner()
+        }
+
+        #[ink(message)]
+        fn pending_owner(&self) -> Option<AccountId> {
+            self.access_control.pending_owner()
+        }
+
+        #[ink(message)]
+        fn has_role(&self, role: Role, account: AccountId) -> bool {
+            self.access_control.has_role(role, account)
+        }
+
+        #[ink(message)]
+        fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .transfer_ownership(self.env().caller(), new_owner)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn accept_ownership(&mut self) -> Result<(), AccessControlError> {
+            let events = self.access_control.accept_ownership(self.env().caller())?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn renounce_ownership(&mut self) -> Result<(), AccessControlError> {
+            let events = self.access_control.renounce_ownership(self.env().caller())?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn grant_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .grant_role(self.env().caller(), role, account)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<(), AccessControlError> {
+            let events = self
+                .access_control
+                .revoke_role(self.env().caller(), role, account)?;
+            self.emit_access_control_events(events);
+            Ok(())
+        }
+
+        #[ink(message)]
+        fn renounce_role(&mut self, role: Role) -> Result<(), AccessControlError> {
+            let events = self.access_control.renounce_role(self.env().caller(), role);
+            self.emit_access_control_events(events);
+            Ok(())
+        }
     }
 
     impl CollateralVault for Vault {
@@ -73,6 +316,10 @@ mod vault {
             let market = self.env().caller();
             let contract = self.env().account_id();
 
+            if self.paused_scopes.contains(&VaultPauseScope::Deposit) {
+                return Err(VaultError::Paused);
+            }
+
             if !self.markets.contains(&market) {
                 return Err(VaultError::MarketNotFound);
             }
@@ -122,6 +369,10 @@ mod vault {
         ) -> Result<(), VaultError> {
             let market = self.env().caller();
 
+            if self.paused_scopes.contains(&VaultPauseScope::Withdraw) {
+                return Err(VaultError::Paused);
+            }
+
             if !self.markets.contains(&market) {
                 return Err(VaultError::MarketNotFound);
             }
@@ -165,11 +416,7 @@ mod vault {
 
         #[ink(message)]
         fn add_asset(&mut self, collateral_asset: AccountId) -> Result<(), VaultError> {
-            let caller = self.env().caller();
-
-            if caller != self.admin {
-                return Err(VaultError::NotAdmin);
-            }
+            self.ensure_role(Role::Admin)?;
 
             if self.assets.contains(&collateral_asset) {
                 return Err(VaultError::AssetAlreadyExist);
@@ -182,11 +429,7 @@ mod vault {
 
         #[ink(message)]
         fn add_market(&mut self, market: AccountId) -> Result<(), VaultError> {
-            let caller = self.env().caller();
-
-            if caller != self.admin {
-                return Err(VaultError::NotAdmin);
-            }
+            self.ensure_role(Role::Admin)?;
 
             if self.markets.contains(&market) {
                 return Err(VaultError::MarketAlreadyExist);
@@ -400,6 +643,210 @@ mod vault {
             Ok(())
         }
 
+        #[ink_e2e::test]
+        async fn pause_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let balance = 500_000_000u128;
+            let deposit_amount = 100_000_000u128;
+            let alice = &ink_e2e::alice();
+            let bob = &ink_e2e::bob();
+            let alice_account = ink_e2e::account_id(ink_e2e::AccountKeyring::Alice);
+
+            let vault_constructor = VaultRef::new();
+            let vault_acc_id: AccountId = client
+                .instantiate("vault", alice, vault_constructor, 0, None)
+                .await
+                .expect("Instantiate failed")
+                .account_id;
+
+            let token_constructor = TokenRef::new(balance, None, None, 0);
+            let token_acc_id: AccountId = client
+                .instantiate("psp22", alice, token_constructor, 0, None)
+                .await
+                .expect("Instantiate failed")
+                .account_id;
+
+            let token_approve = build_message::<TokenRef>(token_acc_id.clone())
+                .call(|token| token.approve(vault_acc_id.clone(), balance));
+            let _token_approve_res = client
+                .call(alice, token_approve, 0, None)
+                .await
+                .expect("token approve failed");
+
+            let add_market = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.add_market(alice_account));
+            let _add_market_res = client
+                .call(alice, add_market, 0, None)
+                .await
+                .expect("add_market failed");
+
+            let add_asset = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.add_asset(token_acc_id.clone()));
+            let _add_asset_res = client
+                .call(alice, add_asset, 0, None)
+                .await
+                .expect("add_asset failed");
+
+            let pause_not_guardian = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.set_paused(VaultPauseScope::Deposit, true));
+            let pause_not_guardian_res = client.call(bob, pause_not_guardian, 0, None).await;
+            assert!(
+                pause_not_guardian_res.is_err(),
+                "pause not called by guardian"
+            );
+
+            let pause = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.set_paused(VaultPauseScope::Deposit, true));
+            let _pause_res = client
+                .call(alice, pause, 0, None)
+                .await
+                .expect("pause failed");
+
+            let deposit_paused = build_message::<VaultRef>(vault_acc_id.clone()).call(|vault| {
+                vault.deposit(alice_account, 0, token_acc_id.clone(), deposit_amount)
+            });
+            let deposit_paused_res = client.call(alice, deposit_paused, 0, None).await;
+            assert!(deposit_paused_res.is_err(), "deposit while paused");
+
+            let unpause = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.set_paused(VaultPauseScope::Deposit, false));
+            let _unpause_res = client
+                .call(alice, unpause, 0, None)
+                .await
+                .expect("unpause failed");
+
+            let deposit = build_message::<VaultRef>(vault_acc_id.clone()).call(|vault| {
+                vault.deposit(alice_account, 0, token_acc_id.clone(), deposit_amount)
+            });
+            let deposit_res = client.call(alice, deposit, 0, None).await;
+            assert!(deposit_res.is_ok(), "deposit should succeed after unpause");
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn access_control_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = &ink_e2e::alice();
+            let bob = &ink_e2e::bob();
+            let bob_account = ink_e2e::account_id(ink_e2e::AccountKeyring::Bob);
+
+            let vault_constructor = VaultRef::new();
+            let vault_acc_id: AccountId = client
+                .instantiate("vault", alice, vault_constructor, 0, None)
+                .await
+                .expect("Instantiate failed")
+                .account_id;
+
+            let grant_not_admin = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.grant_role(Role::Guardian, bob_account));
+            let grant_not_admin_res = client.call(bob, grant_not_admin, 0, None).await;
+            assert!(grant_not_admin_res.is_err(), "grant_role not called by admin");
+
+            let grant = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.grant_role(Role::Guardian, bob_account));
+            let _grant_res = client
+                .call(alice, grant, 0, None)
+                .await
+                .expect("grant_role failed");
+
+            let has_role = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.has_role(Role::Guardian, bob_account));
+            let has_role_res = client
+                .call_dry_run(alice, &has_role, 0, None)
+                .await
+                .return_value();
+            assert!(has_role_res, "bob should be a guardian");
+
+            let pause = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.set_paused(VaultPauseScope::Deposit, true));
+            let _pause_res = client
+                .call(bob, pause, 0, None)
+                .await
+                .expect("pause by guardian failed");
+
+            let add_asset_guardian = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.add_asset(bob_account));
+            let add_asset_guardian_res = client.call(bob, add_asset_guardian, 0, None).await;
+            assert!(add_asset_guardian_res.is_err(), "add_asset not called by admin");
+
+            let transfer = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.transfer_ownership(bob_account));
+            let _transfer_res = client
+                .call(alice, transfer, 0, None)
+                .await
+                .expect("transfer_ownership failed");
+
+            let owner = build_message::<VaultRef>(vault_acc_id.clone()).call(|vault| vault.owner());
+            let owner_res = client
+                .call_dry_run(alice, &owner, 0, None)
+                .await
+                .return_value();
+            assert_ne!(owner_res, bob_account, "ownership changed before accept");
+
+            let accept = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.accept_ownership());
+            let _accept_res = client
+                .call(bob, accept, 0, None)
+                .await
+                .expect("accept_ownership failed");
+
+            let owner = build_message::<VaultRef>(vault_acc_id.clone()).call(|vault| vault.owner());
+            let owner_res = client
+                .call_dry_run(alice, &owner, 0, None)
+                .await
+                .return_value();
+            assert_eq!(owner_res, bob_account, "bob should be the owner");
+
+            Ok(())
+        }
+
+        #[ink_e2e::test]
+        async fn upgrade_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
+            let alice = &ink_e2e::alice();
+            let bob = &ink_e2e::bob();
+
+            let vault_constructor = VaultRef::new();
+            let vault_acc_id: AccountId = client
+                .instantiate("vault", alice, vault_constructor, 0, None)
+                .await
+                .expect("Instantiate failed")
+                .account_id;
+
+            let code_hash = client
+                .upload("vault", alice, None)
+                .await
+                .expect("upload failed")
+                .code_hash;
+
+            let upgrade_not_owner = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.upgrade(code_hash));
+            let upgrade_not_owner_res = client.call(bob, upgrade_not_owner, 0, None).await;
+            assert!(upgrade_not_owner_res.is_err(), "upgrade not called by owner");
+
+            let upgrade = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.upgrade(code_hash));
+            let _upgrade_res = client
+                .call(alice, upgrade, 0, None)
+                .await
+                .expect("upgrade failed");
+
+            let migrate = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.migrate());
+            let _migrate_res = client
+                .call(alice, migrate, 0, None)
+                .await
+                .expect("migrate failed");
+
+            let storage_version = build_message::<VaultRef>(vault_acc_id.clone())
+                .call(|vault| vault.view_storage_version());
+            let storage_version_res = client
+                .call_dry_run(alice, &storage_version, 0, None)
+                .await
+                .return_value();
+            assert_eq!(storage_version_res, STORAGE_VERSION);
+
+            Ok(())
+        }
+
         #[ink_e2e::test]
         async fn withdraw_works(mut client: ink_e2e::Client<C, E>) -> E2EResult<()> {
             let balance = 500_000_000u128;
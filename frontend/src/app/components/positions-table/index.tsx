@@ -22,16 +22,17 @@ interface RowProps {
 const Row = ({ position, market, fetchPositions }: RowProps) => {
   const { closePosition } = useManagePosition({ marketAddress: market.address })
 
-  const positionPnlPercentage = formatPercentage(parseInt(position.pnlPercentage))
+  const pnlPercentage = formatWithDecimals(position.pnlPercentage, 2)
+  const positionPnlPercentage = formatPercentage(pnlPercentage)
 
   const longShortcolor = position.isLong ? 'text-green-500' : 'text-red-500'
-  const pnlColor = parseInt(position.pnlPercentage) >= 0 ? 'text-green-500' : 'text-red-500'
+  const pnlColor = pnlPercentage >= 0 ? 'text-green-500' : 'text-red-500'
 
   const collateralAmount = formatWithDecimals(position.collateralAmount, market.decimals)
   const collateralUsd = formatWithDecimals(position.collateralUsd, 6)
-  const positionNetValue = collateralUsd + collateralUsd * (parseInt(position.pnlPercentage) / 100)
-  const pnlUsd = positionNetValue - collateralUsd
-  const leverage = parseInt(position.leverage)
+  const pnlUsd = formatWithDecimals(position.pnlUsd, 6)
+  const positionNetValue = collateralUsd + pnlUsd
+  const leverage = formatWithDecimals(position.leverage, 4)
   const size = collateralAmount * leverage
   const sizeUsd = collateralUsd * leverage
 
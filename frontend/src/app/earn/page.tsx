@@ -9,10 +9,11 @@ import { Navbar } from '@/app/components/navbar'
 import { Button } from '@/components/ui/button'
 import { Switcher, SwitcherButton } from '@/components/ui/switcher'
 import { AZERO } from '@/utils/constants'
-import { Market } from '@/utils/types'
+import { formatWithDecimals } from '@/utils/formatters'
+import { Market, WithdrawRequest } from '@/utils/types'
 
 import InputBox from '../components/position-management/input-box'
-import { useEarnDeposit, useEarnWithdraw } from '../hooks/useEarn'
+import { useEarnDeposit, useEarnWithdraw, useWithdrawRequests } from '../hooks/useEarn'
 import { useMarkets } from '../hooks/useMarkets'
 import { useWalletBalance } from '../hooks/useWalletBalance'
 
@@ -41,7 +42,11 @@ export default function Earn() {
     marketAddress: WAZERO?.address || '',
   })
 
-  const { withdraw, withdrawNative } = useEarnWithdraw({
+  const { requestWithdraw, withdraw, withdrawNative, cancelWithdraw } = useEarnWithdraw({
+    marketAddress: WAZERO?.address || '',
+  })
+
+  const { withdrawRequests, currentBlock, fetchWithdrawRequests } = useWithdrawRequests({
     marketAddress: WAZERO?.address || '',
   })
 
@@ -55,15 +60,13 @@ export default function Earn() {
   const inputLabel = isDeposit ? 'Send' : 'Receive'
   const buttonLabel = isDeposit
     ? `Deposit${toWrapOrUnwrap ? ' & Wrap' : ''}`
-    : `Withdraw${toWrapOrUnwrap ? ' & Unwrap' : ''}`
+    : 'Request Withdraw'
 
   const depositOrWithdraw = isDeposit
     ? asset === AZERO
       ? depositNative
       : deposit
-    : asset === AZERO
-      ? withdrawNative
-      : withdraw
+    : requestWithdraw
 
   const formattedAmount = parseInt(String(Number(amount) * 10 ** asset.decimals))
 
@@ -82,9 +85,26 @@ export default function Earn() {
     if (!amount) return
     await depositOrWithdraw({ amount: formattedAmount })
     await fetchDepositBalances()
+    await fetchWithdrawRequests()
     setAmount('')
   }
 
+  const isClaimable = (request: WithdrawRequest) =>
+    currentBlock >= parseInt(request.blockClaimable.replace(/,/g, ''))
+
+  const handleClaim = async (request: WithdrawRequest) => {
+    const id = parseInt(request.id.replace(/,/g, ''))
+    await (asset === AZERO ? withdrawNative : withdraw)({ id })
+    await fetchDepositBalances()
+    await fetchWithdrawRequests()
+  }
+
+  const handleCancel = async (request: WithdrawRequest) => {
+    await cancelWithdraw({ id: parseInt(request.id.replace(/,/g, '')) })
+    await fetchDepositBalances()
+    await fetchWithdrawRequests()
+  }
+
   useEffect(() => {
     setAmount('')
   }, [isDeposit])
@@ -169,6 +189,41 @@ export default function Earn() {
                 <span className="text-[1.1em] font-bold">{buttonLabel}</span>
               </Button>
             </div>
+            {withdrawRequests.length > 0 && (
+              <div className="flex min-w-[35em] flex-col gap-2 rounded bg-violet-950 p-4">
+                <Subtitle>Pending withdrawals</Subtitle>
+                {withdrawRequests.map((request) => (
+                  <div
+                    key={request.id}
+                    className="flex flex-row items-center justify-between gap-4 text-white"
+                  >
+                    <span className="font-bold">
+                      {formatWithDecimals(request.shares, AZERO.decimals).toFixed(2)} xAZERO
+                    </span>
+                    <span>
+                      {isClaimable(request)
+                        ? 'Claimable'
+                        : `Claimable at block ${request.blockClaimable}`}
+                    </span>
+                    <div className="flex gap-2">
+                      <Button
+                        className="rounded-[0.35em]"
+                        onClick={() => handleClaim(request)}
+                        disabled={!isClaimable(request)}
+                      >
+                        <span className="font-bold">Claim</span>
+                      </Button>
+                      <Button
+                        className="rounded-[0.35em] bg-violet-600"
+                        onClick={() => handleCancel(request)}
+                      >
+                        <span className="font-bold">Cancel</span>
+                      </Button>
+                    </div>
+                  </div>
+                ))}
+              </div>
+            )}
           </div>
         </div>
       </div>
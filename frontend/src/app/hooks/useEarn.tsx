@@ -1,10 +1,12 @@
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 
 import marketAbi from '@/abis/market.json'
-import { useContract, useInkathon } from '@scio-labs/use-inkathon'
+import { ContractPromise } from '@polkadot/api-contract'
+import { contractQuery, decodeOutput, useContract, useInkathon } from '@scio-labs/use-inkathon'
 import toast from 'react-hot-toast'
 
 import { contractTxWithToast } from '@/utils/contract-tx-with-toast'
+import { WithdrawRequest } from '@/utils/types'
 
 interface useEarnDepositProps {
   marketAddress: string
@@ -70,49 +72,141 @@ export const useEarnWithdraw = ({ marketAddress }: useEarnDepositProps) => {
   const { api, activeAccount } = useInkathon()
   const { contract: marketContract } = useContract(marketAbi, marketAddress)
 
+  const [requestWithdrawIsLoading, setRequestWithdrawIsLoading] = useState<boolean>(false)
   const [withdrawIsLoading, setWithdrawIsLoading] = useState<boolean>(false)
   const [withdrawNativeIsLoading, setWithdrawNativeIsLoading] = useState<boolean>(false)
+  const [cancelWithdrawIsLoading, setCancelWithdrawIsLoading] = useState<boolean>(false)
 
-  const withdraw = async ({ amount }: { amount: number }) => {
+  const requestWithdraw = async ({ amount }: { amount: number }) => {
     if (!activeAccount || !marketContract || !api) {
       toast.error('Wallet not connected. Try again…')
       return
     }
 
-    setWithdrawIsLoading(true)
+    setRequestWithdrawIsLoading(true)
     try {
-      await contractTxWithToast(api, activeAccount.address, marketContract, 'withdraw', {}, [
+      await contractTxWithToast(api, activeAccount.address, marketContract, 'request_withdraw', {}, [
         amount,
       ])
     } catch (e) {
       console.error(e)
+    } finally {
+      setRequestWithdrawIsLoading(false)
+    }
+  }
+
+  const withdraw = async ({ id }: { id: number }) => {
+    if (!activeAccount || !marketContract || !api) {
+      toast.error('Wallet not connected. Try again…')
+      return
+    }
+
+    setWithdrawIsLoading(true)
+    try {
+      await contractTxWithToast(api, activeAccount.address, marketContract, 'withdraw', {}, [id])
+    } catch (e) {
+      console.error(e)
     } finally {
       setWithdrawIsLoading(false)
     }
   }
 
-  const withdrawNative = async ({ amount }: { amount: number }) => {
+  const cancelWithdraw = async ({ id }: { id: number }) => {
     if (!activeAccount || !marketContract || !api) {
       toast.error('Wallet not connected. Try again…')
       return
     }
 
-    setWithdrawNativeIsLoading(true)
+    setCancelWithdrawIsLoading(true)
     try {
-      await contractTxWithToast(api, activeAccount.address, marketContract, 'withdraw_native', {}, [
-        amount,
+      await contractTxWithToast(api, activeAccount.address, marketContract, 'cancel_withdraw', {}, [
+        id,
       ])
     } catch (e) {
       console.error(e)
+    } finally {
+      setCancelWithdrawIsLoading(false)
+    }
+  }
+
+  const withdrawNative = async ({ id }: { id: number }) => {
+    if (!activeAccount || !marketContract || !api) {
+      toast.error('Wallet not connected. Try again…')
+      return
+    }
+
+    setWithdrawNativeIsLoading(true)
+    try {
+      await contractTxWithToast(api, activeAccount.address, marketContract, 'withdraw_native', {}, [id])
+    } catch (e) {
+      console.error(e)
     } finally {
       setWithdrawNativeIsLoading(false)
     }
   }
 
   return {
+    requestWithdraw,
     withdraw,
     withdrawNative,
+    cancelWithdraw,
+    requestWithdrawIsLoading,
     withdrawIsLoading,
     withdrawNativeIsLoading,
+    cancelWithdrawIsLoading,
+  }
+}
+
+export const useWithdrawRequests = ({ marketAddress }: useEarnDepositProps) => {
+  const { api, activeAccount } = useInkathon()
+
+  const [withdrawRequests, setWithdrawRequests] = useState<WithdrawRequest[]>([])
+  const [currentBlock, setCurrentBlock] = useState<number>(0)
+
+  const fetchWithdrawRequests = async () => {
+    if (!api || !activeAccount || !marketAddress) return
+
+    try {
+      const marketContract = new ContractPromise(api, marketAbi, marketAddress)
+      const result = await contractQuery(
+        api,
+        activeAccount.address,
+        marketContract,
+        'view_withdraw_requests',
+        {},
+        [activeAccount.address],
+      )
+      const {
+        output: requestsData,
+        isError: isError,
+        decodedOutput: decodedOutput,
+      } = decodeOutput(result, marketContract, 'view_withdraw_requests')
+      if (isError) throw new Error(decodedOutput)
+
+      const header = await api.rpc.chain.getHeader()
+      setCurrentBlock(header.number.toNumber())
+      setWithdrawRequests(
+        requestsData.map((request: any) => ({
+          user: request.user,
+          id: request.id,
+          shares: request.shares,
+          blockRequested: request.blockRequested,
+          blockClaimable: request.blockClaimable,
+        })),
+      )
+    } catch (e) {
+      console.error(e)
+      toast.error('Error while fetching withdraw requests. Try again…')
+    }
+  }
+
+  useEffect(() => {
+    fetchWithdrawRequests()
+  }, [api, activeAccount, marketAddress])
+
+  return {
+    withdrawRequests,
+    currentBlock,
+    fetchWithdrawRequests,
   }
 }
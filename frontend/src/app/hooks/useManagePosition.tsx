@@ -5,6 +5,7 @@ import { useContract, useInkathon } from '@scio-labs/use-inkathon'
 import toast from 'react-hot-toast'
 
 import { contractTxWithToast } from '@/utils/contract-tx-with-toast'
+import { BASIS_POINTS } from '@/utils/constants'
 
 interface useManagePositionProps {
   marketAddress: string
@@ -39,7 +40,7 @@ export const useManagePosition = ({ marketAddress }: useManagePositionProps) =>
         marketContract,
         'open_native',
         { value: amount },
-        [isLong, leverage],
+        [isLong, Math.round(leverage * BASIS_POINTS), null, null],
       )
     } catch (e) {
       console.error(e)
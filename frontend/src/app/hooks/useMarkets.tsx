@@ -8,6 +8,7 @@ import { ContractPromise } from '@polkadot/api-contract'
 import { contractQuery, decodeOutput, useInkathon } from '@scio-labs/use-inkathon'
 import toast from 'react-hot-toast'
 
+import { BASIS_POINTS } from '@/utils/constants'
 import { formatWithDecimals } from '@/utils/formatters'
 import { Market } from '@/utils/types'
 
@@ -155,8 +156,7 @@ export const useMarkets = () => {
     try {
       const marketContract = new ContractPromise(api, marketAbi, marketAddress)
       const result = await contractQuery(api, '', marketContract, 'view_liquidation_price', {}, [
-        marketAddress,
-        leverage,
+        Math.round(leverage * BASIS_POINTS),
         isLong,
       ])
       const {
@@ -35,8 +35,10 @@ export const usePositions = ({ markets }: usePositionsProps) => {
 
       for (const positionData of marketPositionsData.Ok) {
         const position = positionData[0]
-        const pnlPercentage = positionData[1]
-        const price = positionData[2]
+        const pnlUsd = positionData[1]
+        const pnlPercentage = positionData[2]
+        const price = positionData[3]
+        const maxProfitUsd = positionData[4]
         const marketPosition: MarketPosition = {
           user: position.user,
           id: position.id,
@@ -48,8 +50,10 @@ export const usePositions = ({ markets }: usePositionsProps) => {
           leverage: position.leverage,
           isLong: position.isLong,
           blockOpen: position.blockOpen,
+          pnlUsd,
           pnlPercentage,
           price,
+          maxProfitUsd,
         }
         positions.push(marketPosition)
       }
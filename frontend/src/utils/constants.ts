@@ -1,5 +1,8 @@
 import { Market, Position, PositionType } from './types'
 
+// leverage and pnl are exchanged with the market contract in basis points
+export const BASIS_POINTS = 10_000
+
 // export const SymbolsToAssets: { [symbol: string]: Asset } = {
 //   [BTC.symbol]: BTC,
 //   [AZERO.symbol]: AZERO,
@@ -33,6 +33,16 @@ export interface MarketPosition {
   leverage: string
   isLong: boolean
   blockOpen: string
+  pnlUsd: string
   pnlPercentage: string
   price: string
+  maxProfitUsd: string
+}
+
+export interface WithdrawRequest {
+  user: string
+  id: string
+  shares: string
+  blockRequested: string
+  blockClaimable: string
 }